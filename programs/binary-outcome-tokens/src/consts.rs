@@ -1,3 +1,10 @@
 
 
-pub const CONDITION_AUTH_PDA_SEED: &[u8] = b"condition_auth_pda_seed";
\ No newline at end of file
+pub const CONDITION_AUTH_PDA_SEED: &[u8] = b"condition_auth_pda_seed";
+
+pub const WALLET_POSITION_SEED: &[u8] = b"wallet_position_seed";
+
+// bounded by compute, conversions do a token CPI per member
+pub const MAX_GROUP_CONDITIONS: usize = 10;
+
+pub const BPS_DENOMINATOR: u64 = 10_000;
\ No newline at end of file
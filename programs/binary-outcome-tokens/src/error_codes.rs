@@ -25,4 +25,60 @@ pub enum ErrorCodes{
     OutcomeTokenNotWinner,
     #[msg("Outcome tokens should be different")]
     SameToken,
+    #[msg("Gate mode should be 0, 1 or 2")]
+    InvalidGateMode,
+    #[msg("Wallet is not allowed to open positions in this condition")]
+    WalletNotAllowed,
+    #[msg("Condition is gated, a registered wallet position is required")]
+    WalletPositionRequired,
+    #[msg("Wallet collateral cap exceeded")]
+    WalletCapExceeded,
+    #[msg("Condition collateral cap exceeded")]
+    GlobalCapExceeded,
+    #[msg("Amount overflow")]
+    AmountOverflow,
+    #[msg("condition_auth_pda must be the freeze_authority of the token if one is set")]
+    InvalidFreezeAuthority,
+    #[msg("Outcome tokens can't be frozen by the condition")]
+    ConditionNotFreezable,
+    #[msg("Condition resolution is under dispute")]
+    ConditionDisputed,
+    #[msg("Condition resolution is not under dispute")]
+    ConditionNotDisputed,
+    #[msg("Dispute status should be either 0 or 1")]
+    InvalidDisputeStatus,
+    #[msg("Token accounts owned by a program address can't be frozen")]
+    ProgramOwnedAccount,
+    #[msg("Condition group is full")]
+    ConditionGroupFull,
+    #[msg("Condition already belongs to a group")]
+    ConditionAlreadyGrouped,
+    #[msg("Condition collateral doesn't match the group")]
+    GroupCollateralMismatch,
+    #[msg("Condition belongs to a group, the group account is required")]
+    ConditionGroupRequired,
+    #[msg("Condition group doesn't match the condition")]
+    InvalidConditionGroup,
+    #[msg("Another condition in the group already resolved YES")]
+    GroupWinnerAlreadySet,
+    #[msg("Condition group is already settled")]
+    GroupAlreadySettled,
+    #[msg("Condition group is not settled yet")]
+    GroupNotSettled,
+    #[msg("Conversion needs at least 2 conditions in the group")]
+    GroupTooSmall,
+    #[msg("Accounts don't match the group members")]
+    InvalidGroupAccounts,
+    #[msg("Gated conditions can't be the kept member of a conversion")]
+    ConversionGated,
+    #[msg("A flash mint is already outstanding for this condition")]
+    FlashMintOutstanding,
+    #[msg("No flash mint is outstanding for this condition")]
+    NoFlashMintOutstanding,
+    #[msg("Flash mint must be followed by a flash_repay instruction in the same transaction")]
+    FlashRepayMissing,
+    #[msg("Gated conditions don't allow flash minting")]
+    FlashMintGated,
+    #[msg("Fee should be 10000 basis points or less")]
+    InvalidFee,
 }
\ No newline at end of file
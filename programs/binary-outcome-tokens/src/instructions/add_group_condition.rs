@@ -0,0 +1,48 @@
+use anchor_lang::prelude::*;
+
+use crate::consts::MAX_GROUP_CONDITIONS;
+use crate::error_codes::ErrorCodes;
+use crate::state::{Condition, ConditionGroup};
+
+// links an active condition to a group. the signer has to be both the group authority and the condition
+// resolution authority, since joining a group restricts how the condition can be resolved
+pub fn add_group_condition(ctx: Context<AddGroupCondition>) -> Result<()> {
+    let group = &mut ctx.accounts.group;
+    let condition = &mut ctx.accounts.condition;
+
+    if condition.active == 0 {
+        return err!(ErrorCodes::ConditionInactive);
+    }
+    if condition.is_grouped() {
+        return err!(ErrorCodes::ConditionAlreadyGrouped);
+    }
+    if group.settled == 1 {
+        return err!(ErrorCodes::GroupAlreadySettled);
+    }
+    if group.conditions.len() >= MAX_GROUP_CONDITIONS {
+        return err!(ErrorCodes::ConditionGroupFull);
+    }
+    if condition.collateral_token != group.collateral_token
+        || condition.collateral_per_ticket != group.collateral_per_ticket
+    {
+        return err!(ErrorCodes::GroupCollateralMismatch);
+    }
+
+    group.conditions.push(condition.key());
+    condition.group = group.key();
+
+    msg!("added condition {} to the group", condition.key());
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AddGroupCondition<'info> {
+    #[account(constraint = signer.key() == group.authority && signer.key() == condition.resolution_auth)]
+    signer: Signer<'info>,
+
+    #[account(mut)]
+    group: Box<Account<'info, ConditionGroup>>,
+
+    #[account(mut)]
+    condition: Box<Account<'info, Condition>>,
+}
@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::error_codes::ErrorCodes;
+use crate::state::{Condition, GATE_MODE_ATTESTATION};
+
+// lets the resolution authority restrict who can open positions and how much collateral goes in,
+// redeeming is never gated so holders can always exit
+pub fn configure_mint_gate(
+    ctx: Context<ConfigureMintGate>,
+    gate_mode: u64,
+    allowlist_root: [u8; 32],
+    attestation_program: Pubkey,
+    wallet_cap: u64,
+    global_cap: u64,
+) -> Result<()> {
+    if gate_mode > GATE_MODE_ATTESTATION {
+        return err!(ErrorCodes::InvalidGateMode);
+    }
+
+    let condition = &mut ctx.accounts.condition;
+    condition.gate_mode = gate_mode;
+    condition.allowlist_root = allowlist_root;
+    condition.attestation_program = attestation_program;
+    condition.wallet_cap = wallet_cap;
+    condition.global_cap = global_cap;
+
+    msg!(
+        "mint gate set to mode {}, wallet cap {}, global cap {}",
+        gate_mode,
+        wallet_cap,
+        global_cap
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ConfigureMintGate<'info> {
+    #[account(constraint = signer.key() == condition.resolution_auth)]
+    signer: Signer<'info>,
+
+    #[account(mut)]
+    condition: Box<Account<'info, Condition>>,
+}
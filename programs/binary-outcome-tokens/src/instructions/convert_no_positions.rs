@@ -0,0 +1,132 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::CONDITION_AUTH_PDA_SEED;
+use crate::error_codes::ErrorCodes;
+use crate::state::{ConditionGroup, NO_OUTCOME, YES_OUTCOME};
+use crate::utils::{condition_auth_bump, load_group_member};
+
+// converts a NO position on every member but one into YES on the kept member plus freed collateral.
+// at most one member can win, so n-1 NO tokens always pay the same as a YES on the kept member plus n-2 tickets.
+// remaining accounts hold 5 accounts per member, in group order:
+// [condition, condition_auth_pda, collateral_vault, outcome token mint, user outcome token account]
+// the outcome token is YES for the kept member (minted to the user) and NO for the others (burnt from the user).
+// conditions are writable, members releasing collateral get their open interest updated
+pub fn convert_no_positions<'info>(
+    ctx: Context<'_, '_, '_, 'info, ConvertNoPositions<'info>>,
+    tickets_amount: u64,
+    keep_index: u64,
+) -> Result<()> {
+    let group = &ctx.accounts.group;
+    let members = group.conditions.len();
+    let keep_index = keep_index as usize;
+
+    if members < 2 {
+        return err!(ErrorCodes::GroupTooSmall);
+    }
+    if group.settled == 1 {
+        return err!(ErrorCodes::GroupAlreadySettled);
+    }
+    if keep_index >= members || ctx.remaining_accounts.len() != members * 5 {
+        return err!(ErrorCodes::InvalidGroupAccounts);
+    }
+
+    // the freed collateral is taken from the first n-2 converted members, the vaults get rebalanced
+    // by settle_group once the winner is known
+    let mut vaults_to_release = members - 2;
+    let released_per_vault = group
+        .collateral_per_ticket
+        .checked_mul(tickets_amount)
+        .ok_or(ErrorCodes::AmountOverflow)?;
+
+    for (i, accounts) in ctx.remaining_accounts.chunks(5).enumerate() {
+        let mut condition = load_group_member(&accounts[0], &group.conditions[i])?;
+        if condition.active == 0 {
+            return err!(ErrorCodes::ConditionInactive);
+        }
+        if condition.disputed == 1 {
+            return err!(ErrorCodes::ConditionDisputed);
+        }
+
+        // the minted YES would open a position around the mint gate, which can't be checked without
+        // the user's wallet position, so gated members can't be kept
+        if i == keep_index && condition.is_gated() {
+            return err!(ErrorCodes::ConversionGated);
+        }
+
+        let outcome = if i == keep_index { YES_OUTCOME } else { NO_OUTCOME };
+        if accounts[3].key() != condition.outcomes[outcome].token_mint {
+            return err!(ErrorCodes::InvalidTokenMint);
+        }
+
+        let condition_key = condition.key();
+        let bump = condition_auth_bump(&condition_key, &accounts[1])?;
+        let seeds: &[&[&[u8]]] = &[&[
+            CONDITION_AUTH_PDA_SEED,
+            condition_key.as_ref(), &[bump]
+        ]];
+
+        if i == keep_index {
+            // mint YES tokens of the kept member to the user
+            let cpi_accounts = token::MintTo {
+                mint: accounts[3].clone(),
+                to: accounts[4].clone(),
+                authority: accounts[1].clone(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
+            token::mint_to(cpi_ctx, tickets_amount)?;
+            continue;
+        }
+
+        // burn NO tokens from the user wallet
+        let cpi_accounts = token::Burn {
+            mint: accounts[3].clone(),
+            from: accounts[4].clone(),
+            authority: ctx.accounts.signer.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token::burn(cpi_ctx, tickets_amount)?;
+
+        if vaults_to_release > 0 {
+            if accounts[2].key() != condition.collateral_vault {
+                return err!(ErrorCodes::InvalidCollateralVault);
+            }
+            let cpi_accounts = token::Transfer {
+                from: accounts[2].clone(),
+                to: ctx.accounts.receiver.to_account_info(),
+                authority: accounts[1].clone(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
+            token::transfer(cpi_ctx, released_per_vault)?;
+            vaults_to_release -= 1;
+
+            condition.stats.remove_open_interest(tickets_amount);
+            condition.exit(&crate::ID)?;
+        }
+    }
+
+    msg!(
+        "converted {} NO positions into YES on member {} and {} collateral",
+        tickets_amount,
+        keep_index,
+        released_per_vault * (members as u64 - 2)
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(tickets_amount: u64, keep_index: u64)]
+pub struct ConvertNoPositions<'info> {
+    signer: Signer<'info>,
+
+    group: Box<Account<'info, ConditionGroup>>,
+
+    #[account(mut, constraint = receiver.mint.key() == group.collateral_token)]
+    receiver: Account<'info, TokenAccount>,
+
+    token_program: Program<'info, Token>,
+}
@@ -0,0 +1,111 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::Discriminator;
+use anchor_spl::token;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use crate::consts::CONDITION_AUTH_PDA_SEED;
+use crate::error_codes::ErrorCodes;
+use crate::state::{AuthAccount, Condition};
+
+// mints outcome sets without collateral, as long as a flash_repay for the same condition comes
+// later in the transaction. the repay either burns the sets back or pays their collateral, plus the fee
+pub fn flash_mint(ctx: Context<FlashMint>, tickets_amount: u64) -> Result<()> {
+    let condition = &ctx.accounts.condition;
+
+    if condition.active == 0 {
+        return err!(ErrorCodes::ConditionInactive);
+    }
+    if condition.disputed == 1 {
+        return err!(ErrorCodes::ConditionDisputed);
+    }
+    // repaying with collateral would open positions without going through the gate
+    if condition.is_gated() {
+        return err!(ErrorCodes::FlashMintGated);
+    }
+    if condition.flash_outstanding > 0 {
+        return err!(ErrorCodes::FlashMintOutstanding);
+    }
+
+    find_flash_repay(&ctx.accounts.instructions, &condition.key())?;
+
+    let condition_key = condition.key();
+    let bump = *ctx.bumps.get("condition_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[
+        CONDITION_AUTH_PDA_SEED,
+        condition_key.as_ref(), &[bump]
+    ]];
+
+    // mint outcome 1 tokens to the receiver
+    let cpi_accounts = token::MintTo {
+        mint: ctx.accounts.outcome_1_token.to_account_info(),
+        to: ctx.accounts.receiver_1.to_account_info(),
+        authority: ctx.accounts.condition_auth_pda.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
+    token::mint_to(cpi_ctx, tickets_amount)?;
+
+    // mint outcome 2 tokens to the receiver
+    let cpi_accounts = token::MintTo {
+        mint: ctx.accounts.outcome_2_token.to_account_info(),
+        to: ctx.accounts.receiver_2.to_account_info(),
+        authority: ctx.accounts.condition_auth_pda.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
+    token::mint_to(cpi_ctx, tickets_amount)?;
+
+    ctx.accounts.condition.flash_outstanding = tickets_amount;
+    msg!("flash minted {} sets", tickets_amount);
+    Ok(())
+}
+
+// looks for a flash_repay on the same condition after the current top level instruction,
+// the condition is the second account of flash_repay
+fn find_flash_repay(instructions: &AccountInfo, condition: &Pubkey) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)? as usize;
+    let mut i = current_index + 1;
+    while let Ok(ix) = load_instruction_at_checked(i, instructions) {
+        if ix.program_id == crate::ID
+            && ix.data.len() >= 8
+            && ix.data[..8] == crate::instruction::FlashRepay::DISCRIMINATOR
+            && ix.accounts.get(1).map(|meta| meta.pubkey) == Some(*condition)
+        {
+            return Ok(());
+        }
+        i += 1;
+    }
+    err!(ErrorCodes::FlashRepayMissing)
+}
+
+#[derive(Accounts)]
+#[instruction(tickets_amount: u64)]
+pub struct FlashMint<'info> {
+    signer: Signer<'info>,
+
+    #[account(mut)]
+    condition: Box<Account<'info, Condition>>,
+
+    #[account(seeds = [CONDITION_AUTH_PDA_SEED, condition.key().as_ref()], bump)]
+    condition_auth_pda: Account<'info, AuthAccount>,
+
+    #[account(mut, constraint = receiver_1.mint.key() == outcome_1_token.key())]
+    receiver_1: Account<'info, TokenAccount>,
+    #[account(mut, constraint = receiver_2.mint.key() == outcome_2_token.key())]
+    receiver_2: Account<'info, TokenAccount>,
+
+    #[account(mut, constraint = outcome_1_token.key() == condition.outcomes[0].token_mint)]
+    pub outcome_1_token: Account<'info, Mint>,
+
+    #[account(mut, constraint = outcome_2_token.key() == condition.outcomes[1].token_mint)]
+    pub outcome_2_token: Account<'info, Mint>,
+
+    /// CHECK: the instructions sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    instructions: UncheckedAccount<'info>,
+
+    token_program: Program<'info, Token>,
+}
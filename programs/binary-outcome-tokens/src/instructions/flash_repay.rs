@@ -0,0 +1,128 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use crate::consts::BPS_DENOMINATOR;
+use crate::error_codes::ErrorCodes;
+use crate::state::Condition;
+
+// closes the outstanding flash mint. sets_to_burn sets are burnt back, the rest of the outstanding
+// sets stay in circulation and get paid in collateral. the fee is charged on every borrowed set
+pub fn flash_repay(ctx: Context<FlashRepay>, sets_to_burn: u64) -> Result<()> {
+    let outstanding = ctx.accounts.condition.flash_outstanding;
+    if outstanding == 0 {
+        return err!(ErrorCodes::NoFlashMintOutstanding);
+    }
+    if sets_to_burn > outstanding {
+        return err!(ErrorCodes::NotEnoughTokens);
+    }
+
+    let collateral_per_ticket = ctx.accounts.condition.collateral_per_ticket;
+    let collateral_owed = (outstanding - sets_to_burn)
+        .checked_mul(collateral_per_ticket)
+        .ok_or(ErrorCodes::AmountOverflow)?;
+    let fee = (outstanding as u128)
+        .checked_mul(collateral_per_ticket as u128)
+        .and_then(|fee| fee.checked_mul(ctx.accounts.condition.flash_fee_bps as u128))
+        .ok_or(ErrorCodes::AmountOverflow)?
+        / BPS_DENOMINATOR as u128;
+    let fee = u64::try_from(fee).map_err(|_| error!(ErrorCodes::AmountOverflow))?;
+
+    if sets_to_burn > 0 {
+        // burn outcome 1 tokens from the payer wallet
+        let cpi_accounts = token::Burn {
+            mint: ctx.accounts.outcome_1_token.to_account_info(),
+            authority: ctx.accounts.signer.to_account_info(),
+            from: ctx.accounts.payer_1.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token::burn(cpi_ctx, sets_to_burn)?;
+
+        // burn outcome 2 tokens from the payer wallet
+        let cpi_accounts = token::Burn {
+            mint: ctx.accounts.outcome_2_token.to_account_info(),
+            authority: ctx.accounts.signer.to_account_info(),
+            from: ctx.accounts.payer_2.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token::burn(cpi_ctx, sets_to_burn)?;
+    }
+
+    if collateral_owed > 0 {
+        // collateral for the sets that stay in circulation goes to the vault
+        let cpi_accounts = token::Transfer {
+            from: ctx.accounts.collateral_payer.to_account_info(),
+            to: ctx.accounts.collateral_vault.to_account_info(),
+            authority: ctx.accounts.signer.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token::transfer(cpi_ctx, collateral_owed)?;
+    }
+
+    if fee > 0 {
+        let cpi_accounts = token::Transfer {
+            from: ctx.accounts.collateral_payer.to_account_info(),
+            to: ctx.accounts.fee_receiver.to_account_info(),
+            authority: ctx.accounts.signer.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token::transfer(cpi_ctx, fee)?;
+    }
+
+    // sets paid in collateral are regular open interest from now on
+    let collateralized_sets = outstanding - sets_to_burn;
+    let stats = &mut ctx.accounts.condition.stats;
+    stats.tickets_minted = stats
+        .tickets_minted
+        .checked_add(collateralized_sets)
+        .ok_or(ErrorCodes::AmountOverflow)?;
+    stats.add_open_interest(collateralized_sets)?;
+
+    ctx.accounts.condition.flash_outstanding = 0;
+    msg!(
+        "flash repaid {} sets, burnt {}, collateral {}, fee {}",
+        outstanding,
+        sets_to_burn,
+        collateral_owed,
+        fee
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(sets_to_burn: u64)]
+pub struct FlashRepay<'info> {
+    signer: Signer<'info>,
+
+    // flash_mint looks for the condition at this position, keep it second
+    #[account(mut)]
+    condition: Box<Account<'info, Condition>>,
+
+    #[account(mut, constraint = payer_1.mint.key() == outcome_1_token.key())]
+    payer_1: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = payer_2.mint.key() == outcome_2_token.key())]
+    payer_2: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, constraint = outcome_1_token.key() == condition.outcomes[0].token_mint)]
+    pub outcome_1_token: Box<Account<'info, Mint>>,
+
+    #[account(mut, constraint = outcome_2_token.key() == condition.outcomes[1].token_mint)]
+    pub outcome_2_token: Box<Account<'info, Mint>>,
+
+    #[account(mut, constraint = collateral_payer.mint.key() == condition.collateral_token)]
+    collateral_payer: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, constraint = collateral_vault.key() == condition.collateral_vault @ ErrorCodes::InvalidCollateralVault)]
+    pub collateral_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut,
+    constraint = fee_receiver.mint.key() == condition.collateral_token,
+    constraint = fee_receiver.owner == condition.resolution_auth)]
+    fee_receiver: Box<Account<'info, TokenAccount>>,
+
+    token_program: Program<'info, Token>,
+}
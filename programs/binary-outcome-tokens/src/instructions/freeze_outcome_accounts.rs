@@ -0,0 +1,79 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use crate::consts::CONDITION_AUTH_PDA_SEED;
+use crate::error_codes::ErrorCodes;
+use crate::state::{AuthAccount, Condition};
+
+// freezes the outcome token accounts passed as remaining accounts while the resolution is disputed,
+// so tokens don't change hands at manipulated prices. works in batches, crank style.
+// accounts owned by a program address, like the vaults of a market trading the condition, are rejected
+// since freezing them would lock the program's funds
+pub fn freeze_outcome_accounts<'info>(
+    ctx: Context<'_, '_, '_, 'info, FreezeOutcomeAccounts<'info>>,
+) -> Result<()> {
+    if ctx.accounts.condition.freezable == 0 {
+        return err!(ErrorCodes::ConditionNotFreezable);
+    }
+    if ctx.accounts.condition.disputed == 0 {
+        return err!(ErrorCodes::ConditionNotDisputed);
+    }
+
+    let condition_key = ctx.accounts.condition.key();
+    let bump = *ctx.bumps.get("condition_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[
+        CONDITION_AUTH_PDA_SEED,
+        condition_key.as_ref(), &[bump]
+    ]];
+
+    let mut frozen = 0;
+    for account in ctx.remaining_accounts.iter() {
+        let token_account: Account<TokenAccount> = Account::try_from(account)?;
+        if !token_account.owner.is_on_curve() {
+            return err!(ErrorCodes::ProgramOwnedAccount);
+        }
+        let mint = if token_account.mint == ctx.accounts.outcome_1_token.key() {
+            ctx.accounts.outcome_1_token.to_account_info()
+        } else if token_account.mint == ctx.accounts.outcome_2_token.key() {
+            ctx.accounts.outcome_2_token.to_account_info()
+        } else {
+            return err!(ErrorCodes::InvalidTokenMint);
+        };
+        if token_account.is_frozen() {
+            continue;
+        }
+
+        let cpi_accounts = token::FreezeAccount {
+            account: account.clone(),
+            mint,
+            authority: ctx.accounts.condition_auth_pda.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
+        token::freeze_account(cpi_ctx)?;
+        frozen += 1;
+    }
+
+    msg!("froze {} outcome token accounts", frozen);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct FreezeOutcomeAccounts<'info> {
+    #[account(constraint = signer.key() == condition.resolution_auth)]
+    signer: Signer<'info>,
+
+    condition: Box<Account<'info, Condition>>,
+
+    #[account(seeds = [CONDITION_AUTH_PDA_SEED, condition.key().as_ref()], bump)]
+    condition_auth_pda: Account<'info, AuthAccount>,
+
+    #[account(constraint = outcome_1_token.key() == condition.outcomes[0].token_mint @ ErrorCodes::InvalidTokenMint)]
+    pub outcome_1_token: Account<'info, Mint>,
+
+    #[account(constraint = outcome_2_token.key() == condition.outcomes[1].token_mint @ ErrorCodes::InvalidTokenMint)]
+    pub outcome_2_token: Account<'info, Mint>,
+
+    token_program: Program<'info, Token>,
+}
@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{Condition, ConditionStats};
+
+// view instruction, the counters are returned through return data so clients can simulate it
+pub fn get_condition_stats(ctx: Context<GetConditionStats>) -> Result<ConditionStats> {
+    Ok(ctx.accounts.condition.stats)
+}
+
+#[derive(Accounts)]
+pub struct GetConditionStats<'info> {
+    condition: Box<Account<'info, Condition>>,
+}
@@ -51,10 +51,18 @@ pub fn initialize_condition(
         },
     ];
 
-    ctx.accounts.condition.resolution_auth = ctx.accounts.signer.key();
+    ctx.accounts.condition.resolution_auth = match &ctx.accounts.resolution_auth {
+        Some(resolution_auth) => resolution_auth.key(),
+        None => ctx.accounts.signer.key(),
+    };
     ctx.accounts.condition.collateral_token = ctx.accounts.collateral_token.key();
     ctx.accounts.condition.collateral_per_ticket = collateral_per_ticket;
     ctx.accounts.condition.collateral_vault = ctx.accounts.collateral_vault.key();
+
+    // outcome tokens can only be frozen during disputes if the PDA holds the freeze authority of both
+    let freezable = ctx.accounts.outcome_token_1.freeze_authority.is_some()
+        && ctx.accounts.outcome_token_2.freeze_authority.is_some();
+    ctx.accounts.condition.freezable = freezable as u64;
     Ok(())
 }
 
@@ -94,6 +102,10 @@ pub struct InitializeCondition<'info> {
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
+
+    /// CHECK: only its key is stored, resolves the condition instead of the signer when set,
+    /// a program can then resolve it through one of its PDAs
+    pub resolution_auth: Option<UncheckedAccount<'info>>,
 }
 
 trait TokenCheck {
@@ -116,6 +128,13 @@ impl TokenCheck for Mint {
         if self.mint_authority.unwrap() != authority {
             return err!(ErrorCodes::InvalidTokenMintAuthority);
         }
+
+        // a freeze authority held by anyone else could lock holders out of redeeming
+        if let Some(freeze_authority) = Option::<Pubkey>::from(self.freeze_authority) {
+            if freeze_authority != authority {
+                return err!(ErrorCodes::InvalidFreezeAuthority);
+            }
+        }
         Ok(())
     }
 }
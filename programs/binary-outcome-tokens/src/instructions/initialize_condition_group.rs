@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Mint;
+
+use crate::state::ConditionGroup;
+
+// creates an empty group, conditions are linked to it with add_group_condition
+pub fn initialize_condition_group(
+    ctx: Context<InitializeConditionGroup>,
+    collateral_per_ticket: u64,
+) -> Result<()> {
+    let group = &mut ctx.accounts.group;
+    group.authority = ctx.accounts.signer.key();
+    group.collateral_token = ctx.accounts.collateral_token.key();
+    group.collateral_per_ticket = collateral_per_ticket;
+    group.conditions = Vec::new();
+    group.winner = Pubkey::default();
+    group.settled = 0;
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(collateral_per_ticket: u64)]
+pub struct InitializeConditionGroup<'info> {
+    #[account(mut)]
+    signer: Signer<'info>,
+
+    #[account(init,
+    payer = signer,
+    space = ConditionGroup::MAX_SIZE)]
+    pub group: Box<Account<'info, ConditionGroup>>,
+
+    pub collateral_token: Account<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -56,6 +56,11 @@ pub fn merge_ticket(ctx: Context<MergeTicket>, tickets_amount: u64) -> Result<()
     let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
     token::mint_to(cpi_ctx, tickets_amount)?;
 
+    let stats = &mut ctx.accounts.condition.stats;
+    stats.tickets_merged = stats
+        .tickets_merged
+        .checked_add(tickets_amount)
+        .ok_or(ErrorCodes::AmountOverflow)?;
 
     Ok(())
 }
@@ -66,6 +71,7 @@ pub fn merge_ticket(ctx: Context<MergeTicket>, tickets_amount: u64) -> Result<()
 pub struct MergeTicket<'info> {
     signer: Signer<'info>,
 
+    #[account(mut)]
     condition: Box<Account<'info, Condition>>,
 
     #[account(mut, seeds = [CONDITION_AUTH_PDA_SEED, condition.key().as_ref()], bump)]
@@ -2,9 +2,9 @@ use anchor_lang::prelude::*;
 use anchor_spl::token;
 use anchor_spl::token::{Mint, Token, TokenAccount};
 
-use crate::consts::CONDITION_AUTH_PDA_SEED;
+use crate::consts::{CONDITION_AUTH_PDA_SEED, WALLET_POSITION_SEED};
 use crate::error_codes::ErrorCodes;
-use crate::state::{AuthAccount, Condition};
+use crate::state::{AuthAccount, Condition, WalletPosition};
 
 
 // would deposit collateral to the condition and mint a ticket
@@ -15,13 +15,43 @@ pub fn mint_ticket(ctx: Context<MintTicket>, tickets_amount: u64) -> Result<()>
         return err!(ErrorCodes::ConditionInactive);
     }
 
-    let tickets_cost = ctx.accounts.condition.collateral_per_ticket * tickets_amount;
+    let tickets_cost = ctx
+        .accounts
+        .condition
+        .collateral_per_ticket
+        .checked_mul(tickets_amount)
+        .ok_or(ErrorCodes::AmountOverflow)?;
 
     // Check that the payer has enough tokens
     if ctx.accounts.payer.amount < tickets_cost {
         return err!(ErrorCodes::NotEnoughTokens);
     }
 
+    // gated conditions only let registered wallets in, and within the collateral caps
+    if ctx.accounts.condition.is_gated() {
+        let global_cap = ctx.accounts.condition.global_cap;
+        let vault_amount = ctx
+            .accounts
+            .collateral_vault
+            .amount
+            .checked_add(tickets_cost)
+            .ok_or(ErrorCodes::GlobalCapExceeded)?;
+        if global_cap > 0 && vault_amount > global_cap {
+            return err!(ErrorCodes::GlobalCapExceeded);
+        }
+
+        let position = ctx
+            .accounts
+            .wallet_position
+            .as_mut()
+            .ok_or(ErrorCodes::WalletPositionRequired)?;
+        position.add_collateral(tickets_cost, ctx.accounts.condition.wallet_cap)?;
+        position.unsplit_tickets = position
+            .unsplit_tickets
+            .checked_add(tickets_amount)
+            .ok_or(ErrorCodes::AmountOverflow)?;
+    }
+
     // transfer tokens to the vault
     let cpi_accounts = token::Transfer {
         from: ctx.accounts.payer.to_account_info(),
@@ -32,7 +62,7 @@ pub fn mint_ticket(ctx: Context<MintTicket>, tickets_amount: u64) -> Result<()>
     let cpi_program = ctx.accounts.token_program.to_account_info();
     let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
 
-    token::transfer(cpi_ctx, ctx.accounts.condition.collateral_per_ticket * tickets_amount)?;
+    token::transfer(cpi_ctx, tickets_cost)?;
     msg!("transferred {} to the vault", tickets_cost);
 
     // mint tickets to the receiver wallet
@@ -52,6 +82,13 @@ pub fn mint_ticket(ctx: Context<MintTicket>, tickets_amount: u64) -> Result<()>
     let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
     token::mint_to(cpi_ctx, tickets_amount)?;
 
+    let stats = &mut ctx.accounts.condition.stats;
+    stats.tickets_minted = stats
+        .tickets_minted
+        .checked_add(tickets_amount)
+        .ok_or(ErrorCodes::AmountOverflow)?;
+    stats.add_open_interest(tickets_amount)?;
+
     msg!("minted {} tickets to the receiver", tickets_amount);
     Ok(())
 }
@@ -80,5 +117,9 @@ pub struct MintTicket<'info> {
     #[account(mut, constraint = ticket_token_mint.key() == condition.ticket_token_mint @ ErrorCodes::InvalidTokenMint)]
     pub ticket_token_mint: Account<'info, Mint>,
 
+    // only required when the condition is gated
+    #[account(mut, seeds = [WALLET_POSITION_SEED, condition.key().as_ref(), signer.key().as_ref()], bump)]
+    wallet_position: Option<Account<'info, WalletPosition>>,
+
     token_program: Program<'info, Token>,
 }
@@ -5,6 +5,19 @@ pub mod redeem_ticket;
 pub mod merge_ticket;
 pub mod resolve_condition;
 pub mod redeem_payout;
+pub mod configure_mint_gate;
+pub mod register_wallet;
+pub mod set_dispute_status;
+pub mod freeze_outcome_accounts;
+pub mod thaw_outcome_accounts;
+pub mod initialize_condition_group;
+pub mod add_group_condition;
+pub mod convert_no_positions;
+pub mod settle_group;
+pub mod set_flash_fee;
+pub mod flash_mint;
+pub mod flash_repay;
+pub mod get_condition_stats;
 
 pub use initialize_condition::*;
 pub use mint_ticket::*;
@@ -12,4 +25,17 @@ pub use redeem_ticket::*;
 pub use split_ticket::*;
 pub use merge_ticket::*;
 pub use resolve_condition::*;
-pub use redeem_payout::*;
\ No newline at end of file
+pub use redeem_payout::*;
+pub use configure_mint_gate::*;
+pub use register_wallet::*;
+pub use set_dispute_status::*;
+pub use freeze_outcome_accounts::*;
+pub use thaw_outcome_accounts::*;
+pub use initialize_condition_group::*;
+pub use add_group_condition::*;
+pub use convert_no_positions::*;
+pub use settle_group::*;
+pub use set_flash_fee::*;
+pub use flash_mint::*;
+pub use flash_repay::*;
+pub use get_condition_stats::*;
\ No newline at end of file
@@ -3,7 +3,7 @@ use anchor_spl::token::{Token, TokenAccount, Mint};
 
 use crate::consts::CONDITION_AUTH_PDA_SEED;
 use crate::error_codes::ErrorCodes;
-use crate::state::{AuthAccount, Condition};
+use crate::state::{AuthAccount, Condition, ConditionGroup};
 
 // burn one outcome token for the underlying collateral, only winner tokens should be accepted
 pub fn redeem_payout(ctx: Context<RedeemPayout>, tickets_amount: u64) -> Result<()> {
@@ -12,43 +12,64 @@ pub fn redeem_payout(ctx: Context<RedeemPayout>, tickets_amount: u64) -> Result<
         return err!(ErrorCodes::ConditionStillActive);
     }
 
+    // the winner can still change while the resolution is disputed
+    if ctx.accounts.condition.disputed == 1 {
+        return err!(ErrorCodes::ConditionDisputed);
+    }
+
+    // grouped vaults are only solvent on their own after the group got settled
+    if ctx.accounts.condition.is_grouped() {
+        let group = ctx
+            .accounts
+            .group
+            .as_ref()
+            .ok_or(ErrorCodes::ConditionGroupRequired)?;
+        if group.settled == 0 {
+            return err!(ErrorCodes::GroupNotSettled);
+        }
+    }
+
     // check if enough tokens to redeem
     if ctx.accounts.payer.amount < tickets_amount {
         return err!(ErrorCodes::NotEnoughTokens);
     }
 
     for i in ctx.accounts.condition.outcomes.iter() {
-        if i.winner == 1 {
-            if i.token_mint == ctx.accounts.outcome_token.key() {
-                // burn outcome tokens from the payer wallet
-                let cpi_accounts = anchor_spl::token::Burn {
-                    mint: ctx.accounts.outcome_token.to_account_info(),
-                    authority: ctx.accounts.signer.to_account_info(),
-                    from: ctx.accounts.payer.to_account_info(),
-                };
-                let cpi_program = ctx.accounts.token_program.to_account_info();
-                let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-                anchor_spl::token::burn(cpi_ctx, tickets_amount)?;
-
-                // send 1 collateral token to the receiver wallet
-                let cpi_accounts = anchor_spl::token::Transfer {
-                    from: ctx.accounts.collateral_vault.to_account_info(),
-                    to: ctx.accounts.receiver.to_account_info(),
-                    authority: ctx.accounts.condition_auth_pda.to_account_info(),
-                };
-                let cpi_program = ctx.accounts.token_program.to_account_info();
-                let bump = *ctx.bumps.get("condition_auth_pda").unwrap();
-                let condition_key = ctx.accounts.condition.key();
-                let seeds: &[&[&[u8]]] = &[&[
-                    CONDITION_AUTH_PDA_SEED.as_ref(),
-                    condition_key.as_ref(), &[bump]
-                ]];
-
-                let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
-                anchor_spl::token::transfer(cpi_ctx, ctx.accounts.condition.collateral_per_ticket * tickets_amount)?;
-                msg!("redeemed {} for {}", tickets_amount, ctx.accounts.condition.collateral_per_ticket * tickets_amount);
-                return Ok(());
-            }
+        if i.winner == 1 && i.token_mint == ctx.accounts.outcome_token.key() {
+            // burn outcome tokens from the payer wallet
+            let cpi_accounts = anchor_spl::token::Burn {
+                mint: ctx.accounts.outcome_token.to_account_info(),
+                authority: ctx.accounts.signer.to_account_info(),
+                from: ctx.accounts.payer.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+            anchor_spl::token::burn(cpi_ctx, tickets_amount)?;
+
+            // send 1 collateral token to the receiver wallet
+            let cpi_accounts = anchor_spl::token::Transfer {
+                from: ctx.accounts.collateral_vault.to_account_info(),
+                to: ctx.accounts.receiver.to_account_info(),
+                authority: ctx.accounts.condition_auth_pda.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let bump = *ctx.bumps.get("condition_auth_pda").unwrap();
+            let condition_key = ctx.accounts.condition.key();
+            let seeds: &[&[&[u8]]] = &[&[
+                CONDITION_AUTH_PDA_SEED.as_ref(),
+                condition_key.as_ref(), &[bump]
+            ]];
+
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
+            anchor_spl::token::transfer(cpi_ctx, ctx.accounts.condition.collateral_per_ticket * tickets_amount)?;
+            let stats = &mut ctx.accounts.condition.stats;
+            stats.payout_redeemed = stats
+                .payout_redeemed
+                .checked_add(tickets_amount)
+                .ok_or(ErrorCodes::AmountOverflow)?;
+            stats.remove_open_interest(tickets_amount);
+            msg!("redeemed {} for {}", tickets_amount, ctx.accounts.condition.collateral_per_ticket * tickets_amount);
+            return Ok(());
         }
     };
     err!(ErrorCodes::OutcomeTokenNotWinner)
@@ -78,5 +99,9 @@ pub struct RedeemPayout<'info> {
     #[account(mut, constraint = receiver.mint.key() == condition.collateral_token)]
     receiver: Account<'info, TokenAccount>,
 
+    // only required when the condition belongs to a group
+    #[account(constraint = group.key() == condition.group @ ErrorCodes::InvalidConditionGroup)]
+    group: Option<Box<Account<'info, ConditionGroup>>>,
+
     token_program: Program<'info, Token>,
 }
\ No newline at end of file
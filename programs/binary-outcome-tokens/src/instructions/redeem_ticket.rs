@@ -50,6 +50,13 @@ pub fn redeem_ticket(ctx: Context<RedeemTicket>, tickets_amount: u64) -> Result<
     let refunded_amount = tickets_amount * ctx.accounts.condition.collateral_per_ticket;
     token::transfer(cpi_ctx, refunded_amount)?;
 
+    let stats = &mut ctx.accounts.condition.stats;
+    stats.tickets_redeemed = stats
+        .tickets_redeemed
+        .checked_add(tickets_amount)
+        .ok_or(ErrorCodes::AmountOverflow)?;
+    stats.remove_open_interest(tickets_amount);
+
     msg!("burnt {} tickets for {}", tickets_amount, refunded_amount);
 
     Ok(())
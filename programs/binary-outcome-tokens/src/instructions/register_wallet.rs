@@ -0,0 +1,86 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::keccak;
+
+use crate::consts::WALLET_POSITION_SEED;
+use crate::error_codes::ErrorCodes;
+use crate::state::{Condition, WalletPosition, GATE_MODE_ALLOWLIST, GATE_MODE_ATTESTATION};
+
+// creates the wallet position needed to mint or split in a gated condition.
+// depending on the gate mode the wallet proves it's in the allowlist or holds an attestation account
+pub fn register_wallet(ctx: Context<RegisterWallet>, proof: Vec<[u8; 32]>) -> Result<()> {
+    let condition = &ctx.accounts.condition;
+    let wallet = ctx.accounts.signer.key();
+
+    if condition.active == 0 {
+        return err!(ErrorCodes::ConditionInactive);
+    }
+
+    match condition.gate_mode {
+        GATE_MODE_ALLOWLIST if !verify_allowlist_proof(&proof, condition.allowlist_root, &wallet) => {
+            return err!(ErrorCodes::WalletNotAllowed);
+        }
+        GATE_MODE_ATTESTATION => {
+            let attestation = ctx
+                .accounts
+                .attestation
+                .as_ref()
+                .ok_or(ErrorCodes::WalletNotAllowed)?;
+            check_attestation(attestation, &condition.attestation_program, &wallet)?;
+        }
+        _ => {}
+    }
+
+    let position = &mut ctx.accounts.wallet_position;
+    position.condition = condition.key();
+    position.wallet = wallet;
+    position.collateral = 0;
+    position.unsplit_tickets = 0;
+
+    msg!("registered wallet {}", wallet);
+    Ok(())
+}
+
+// leaves are keccak(wallet), pairs are hashed sorted so proofs don't need direction flags
+fn verify_allowlist_proof(proof: &[[u8; 32]], root: [u8; 32], wallet: &Pubkey) -> bool {
+    let mut node = keccak::hashv(&[wallet.as_ref()]).0;
+    for sibling in proof.iter() {
+        node = if node <= *sibling {
+            keccak::hashv(&[&node, sibling]).0
+        } else {
+            keccak::hashv(&[sibling, &node]).0
+        };
+    }
+    node == root
+}
+
+// an attestation is a live account owned by the attestation program at the PDA derived from the wallet
+fn check_attestation(attestation: &AccountInfo, attestation_program: &Pubkey, wallet: &Pubkey) -> Result<()> {
+    let (expected, _) = Pubkey::find_program_address(&[wallet.as_ref()], attestation_program);
+    if attestation.key() != expected
+        || attestation.owner != attestation_program
+        || attestation.data_is_empty()
+    {
+        return err!(ErrorCodes::WalletNotAllowed);
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RegisterWallet<'info> {
+    #[account(mut)]
+    signer: Signer<'info>,
+
+    condition: Box<Account<'info, Condition>>,
+
+    #[account(init,
+    seeds = [WALLET_POSITION_SEED, condition.key().as_ref(), signer.key().as_ref()],
+    bump,
+    payer = signer,
+    space = WalletPosition::SIZE)]
+    wallet_position: Account<'info, WalletPosition>,
+
+    /// CHECK: only required for attestation gated conditions, owner and address are checked in the instruction
+    attestation: Option<UncheckedAccount<'info>>,
+
+    system_program: Program<'info, System>,
+}
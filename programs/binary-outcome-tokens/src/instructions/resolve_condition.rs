@@ -2,14 +2,45 @@ use anchor_lang::prelude::*;
 
 use crate::consts::CONDITION_AUTH_PDA_SEED;
 use crate::error_codes::ErrorCodes;
-use crate::state::{AuthAccount, Condition};
+use crate::state::{AuthAccount, Condition, ConditionGroup, YES_OUTCOME};
 
 // AnnouncePayout is called by the resolution authority to announce the outcome of the condition.
 pub fn resolve_condition(ctx: Context<ResolveCondition>, outcome : u64) -> Result<()> {
     if outcome > 1 {
         return err!(ErrorCodes::InvalidOutcome);
     }
+    // a resolution can only be changed while it's disputed, payouts are blocked until the dispute is cleared
+    if ctx.accounts.condition.active == 0 && ctx.accounts.condition.disputed == 0 {
+        return err!(ErrorCodes::ConditionNotDisputed);
+    }
+
+    // grouped conditions can't resolve YES if another member already did
+    if ctx.accounts.condition.is_grouped() {
+        let condition_key = ctx.accounts.condition.key();
+        let group = ctx
+            .accounts
+            .group
+            .as_mut()
+            .ok_or(ErrorCodes::ConditionGroupRequired)?;
+        if group.settled == 1 {
+            return err!(ErrorCodes::GroupAlreadySettled);
+        }
+        if group.winner == condition_key {
+            group.winner = Pubkey::default();
+        }
+        if outcome as usize == YES_OUTCOME {
+            if group.winner != Pubkey::default() {
+                return err!(ErrorCodes::GroupWinnerAlreadySet);
+            }
+            group.winner = condition_key;
+        }
+    }
+
     ctx.accounts.condition.active = 0;
+    // clearing previous winners, a disputed condition can be resolved again
+    for i in ctx.accounts.condition.outcomes.iter_mut() {
+        i.winner = 0;
+    }
     ctx.accounts.condition.outcomes[(outcome) as usize].winner = 1;
 
     ctx.accounts.condition.ended_at_slot = Clock::get()?.slot;
@@ -28,4 +59,8 @@ pub struct ResolveCondition<'info> {
 
     #[account(seeds = [CONDITION_AUTH_PDA_SEED, condition.key().as_ref()], bump)]
     condition_auth_pda: Account<'info, AuthAccount>,
+
+    // only required when the condition belongs to a group
+    #[account(mut, constraint = group.key() == condition.group @ ErrorCodes::InvalidConditionGroup)]
+    group: Option<Box<Account<'info, ConditionGroup>>>,
 }
\ No newline at end of file
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::error_codes::ErrorCodes;
+use crate::state::Condition;
+
+// called by the resolution authority when a resolution gets contested (1) and once it's settled (0)
+pub fn set_dispute_status(ctx: Context<SetDisputeStatus>, disputed: u64) -> Result<()> {
+    if disputed > 1 {
+        return err!(ErrorCodes::InvalidDisputeStatus);
+    }
+    ctx.accounts.condition.disputed = disputed;
+
+    msg!("dispute status set to {}", disputed);
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(disputed: u64)]
+pub struct SetDisputeStatus<'info> {
+    #[account(constraint = signer.key() == condition.resolution_auth)]
+    signer: Signer<'info>,
+
+    #[account(mut)]
+    condition: Box<Account<'info, Condition>>,
+}
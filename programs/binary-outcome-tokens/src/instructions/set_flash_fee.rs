@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::consts::BPS_DENOMINATOR;
+use crate::error_codes::ErrorCodes;
+use crate::state::Condition;
+
+// sets the fee charged on flash minted sets, paid to the resolution authority on repayment
+pub fn set_flash_fee(ctx: Context<SetFlashFee>, fee_bps: u64) -> Result<()> {
+    if fee_bps > BPS_DENOMINATOR {
+        return err!(ErrorCodes::InvalidFee);
+    }
+    ctx.accounts.condition.flash_fee_bps = fee_bps;
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(fee_bps: u64)]
+pub struct SetFlashFee<'info> {
+    #[account(constraint = signer.key() == condition.resolution_auth)]
+    signer: Signer<'info>,
+
+    #[account(mut)]
+    condition: Box<Account<'info, Condition>>,
+}
@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use crate::consts::CONDITION_AUTH_PDA_SEED;
+use crate::error_codes::ErrorCodes;
+use crate::state::ConditionGroup;
+use crate::utils::{condition_auth_bump, load_group_member};
+
+// once every member resolved, moves collateral between member vaults so each one can pay its winners.
+// conversions take collateral from vaults before the winner is known, across the group it always adds up.
+// remaining accounts hold 4 accounts per member, in group order:
+// [condition, condition_auth_pda, collateral_vault, winning outcome token mint]
+pub fn settle_group<'info>(ctx: Context<'_, '_, '_, 'info, SettleGroup<'info>>) -> Result<()> {
+    let group = &mut ctx.accounts.group;
+    let members = group.conditions.len();
+
+    if group.settled == 1 {
+        return err!(ErrorCodes::GroupAlreadySettled);
+    }
+    if ctx.remaining_accounts.len() != members * 4 {
+        return err!(ErrorCodes::InvalidGroupAccounts);
+    }
+
+    // surplus (or deficit when negative) of each member vault, with the bump of its auth PDA
+    let mut vaults: Vec<(i128, u8)> = Vec::with_capacity(members);
+    for (i, accounts) in ctx.remaining_accounts.chunks(4).enumerate() {
+        let condition = load_group_member(&accounts[0], &group.conditions[i])?;
+        if condition.active == 1 {
+            return err!(ErrorCodes::ConditionStillActive);
+        }
+        if condition.disputed == 1 {
+            return err!(ErrorCodes::ConditionDisputed);
+        }
+
+        let winner = condition
+            .outcomes
+            .iter()
+            .find(|outcome| outcome.winner == 1)
+            .ok_or(ErrorCodes::InvalidOutcome)?;
+        if accounts[3].key() != winner.token_mint {
+            return err!(ErrorCodes::OutcomeTokenNotWinner);
+        }
+        if accounts[2].key() != condition.collateral_vault {
+            return err!(ErrorCodes::InvalidCollateralVault);
+        }
+
+        let vault: Account<TokenAccount> = Account::try_from(&accounts[2])?;
+        let winner_mint: Account<Mint> = Account::try_from(&accounts[3])?;
+        let required = winner_mint.supply as i128 * condition.collateral_per_ticket as i128;
+        let bump = condition_auth_bump(&condition.key(), &accounts[1])?;
+        vaults.push((vault.amount as i128 - required, bump));
+    }
+
+    // fill every deficit from the vaults with a surplus, whatever is left stays in the vaults
+    let mut from = 0;
+    for to in 0..members {
+        while vaults[to].0 < 0 {
+            while from < members && vaults[from].0 <= 0 {
+                from += 1;
+            }
+            if from == members {
+                return err!(ErrorCodes::NotEnoughTokens);
+            }
+
+            let amount = std::cmp::min(vaults[from].0, -vaults[to].0);
+            let from_accounts = &ctx.remaining_accounts[from * 4..from * 4 + 4];
+            let to_accounts = &ctx.remaining_accounts[to * 4..to * 4 + 4];
+            let condition_key = from_accounts[0].key();
+            let seeds: &[&[&[u8]]] = &[&[
+                CONDITION_AUTH_PDA_SEED,
+                condition_key.as_ref(), &[vaults[from].1]
+            ]];
+
+            let cpi_accounts = token::Transfer {
+                from: from_accounts[2].clone(),
+                to: to_accounts[2].clone(),
+                authority: from_accounts[1].clone(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
+            token::transfer(cpi_ctx, amount as u64)?;
+
+            vaults[from].0 -= amount;
+            vaults[to].0 += amount;
+        }
+    }
+
+    group.settled = 1;
+    msg!("settled condition group with {} members", members);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SettleGroup<'info> {
+    #[account(mut)]
+    group: Box<Account<'info, ConditionGroup>>,
+
+    token_program: Program<'info, Token>,
+}
@@ -2,9 +2,9 @@ use anchor_lang::prelude::*;
 use anchor_spl::token;
 use anchor_spl::token::{Mint, Token, TokenAccount};
 
-use crate::consts::CONDITION_AUTH_PDA_SEED;
+use crate::consts::{CONDITION_AUTH_PDA_SEED, WALLET_POSITION_SEED};
 use crate::error_codes::ErrorCodes;
-use crate::state::{AuthAccount, Condition};
+use crate::state::{AuthAccount, Condition, WalletPosition};
 
 // split one ticket into 2 outcome tokens
 pub fn split_ticket(ctx: Context<SplitTicket>, tickets_amount: u64) -> Result<()> {
@@ -13,12 +13,33 @@ pub fn split_ticket(ctx: Context<SplitTicket>, tickets_amount: u64) -> Result<()
         return err!(ErrorCodes::ConditionInactive);
     }
 
+    // new outcome tokens would escape a dispute freeze
+    if ctx.accounts.condition.disputed == 1 {
+        return err!(ErrorCodes::ConditionDisputed);
+    }
+
 
     // Check that the payer has enough tokens
     if ctx.accounts.payer.amount < tickets_amount {
         return err!(ErrorCodes::NotEnoughTokens);
     }
 
+    // splitting opens outcome positions, so gated conditions check the wallet here too.
+    // tickets the wallet minted itself were already counted against its cap
+    if ctx.accounts.condition.is_gated() {
+        let position = ctx
+            .accounts
+            .wallet_position
+            .as_mut()
+            .ok_or(ErrorCodes::WalletPositionRequired)?;
+        let own_tickets = std::cmp::min(tickets_amount, position.unsplit_tickets);
+        position.unsplit_tickets -= own_tickets;
+        let collateral = (tickets_amount - own_tickets)
+            .checked_mul(ctx.accounts.condition.collateral_per_ticket)
+            .ok_or(ErrorCodes::AmountOverflow)?;
+        position.add_collateral(collateral, ctx.accounts.condition.wallet_cap)?;
+    }
+
     // burn tickets from the payer wallet
     let cpi_accounts = token::Burn {
         mint: ctx.accounts.ticket_token_mint.to_account_info(),
@@ -56,6 +77,12 @@ pub fn split_ticket(ctx: Context<SplitTicket>, tickets_amount: u64) -> Result<()
     let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, seeds);
     token::mint_to(cpi_ctx, tickets_amount)?;
 
+    let stats = &mut ctx.accounts.condition.stats;
+    stats.tickets_split = stats
+        .tickets_split
+        .checked_add(tickets_amount)
+        .ok_or(ErrorCodes::AmountOverflow)?;
+
     msg!("Split {} tickets successful", tickets_amount);
     Ok(())
 }
@@ -90,5 +117,9 @@ pub struct SplitTicket<'info> {
     #[account(mut, constraint = ticket_token_mint.key() == condition.ticket_token_mint)]
     ticket_token_mint: Account<'info, Mint>,
 
+    // only required when the condition is gated
+    #[account(mut, seeds = [WALLET_POSITION_SEED, condition.key().as_ref(), signer.key().as_ref()], bump)]
+    wallet_position: Option<Account<'info, WalletPosition>>,
+
     token_program: Program<'info, Token>,
 }
\ No newline at end of file
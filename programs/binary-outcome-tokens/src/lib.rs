@@ -2,10 +2,11 @@ use anchor_lang::prelude::*;
 
 pub use instructions::*;
 
-mod consts;
+pub mod consts;
 mod error_codes;
 mod instructions;
 pub mod state;
+mod utils;
 
 declare_id!("5c5A6f6HQNhgaSmwuKCkCcgEJWk9UoskR9S2Fp5ig6v1");
 
@@ -54,5 +55,82 @@ pub mod binary_outcome_tokens {
     pub fn redeem_payout(ctx: Context<RedeemPayout>, tickets_amount : u64) -> Result<()> {
         instructions::redeem_payout(ctx,tickets_amount)
     }
+
+    pub fn configure_mint_gate(
+        ctx: Context<ConfigureMintGate>,
+        gate_mode: u64,
+        allowlist_root: [u8; 32],
+        attestation_program: Pubkey,
+        wallet_cap: u64,
+        global_cap: u64,
+    ) -> Result<()> {
+        instructions::configure_mint_gate(
+            ctx,
+            gate_mode,
+            allowlist_root,
+            attestation_program,
+            wallet_cap,
+            global_cap,
+        )
+    }
+
+    pub fn register_wallet(ctx: Context<RegisterWallet>, proof: Vec<[u8; 32]>) -> Result<()> {
+        instructions::register_wallet(ctx, proof)
+    }
+
+    pub fn set_dispute_status(ctx: Context<SetDisputeStatus>, disputed: u64) -> Result<()> {
+        instructions::set_dispute_status(ctx, disputed)
+    }
+
+    pub fn freeze_outcome_accounts<'info>(
+        ctx: Context<'_, '_, '_, 'info, FreezeOutcomeAccounts<'info>>,
+    ) -> Result<()> {
+        instructions::freeze_outcome_accounts(ctx)
+    }
+
+    pub fn thaw_outcome_accounts<'info>(
+        ctx: Context<'_, '_, '_, 'info, ThawOutcomeAccounts<'info>>,
+    ) -> Result<()> {
+        instructions::thaw_outcome_accounts(ctx)
+    }
+
+    pub fn initialize_condition_group(
+        ctx: Context<InitializeConditionGroup>,
+        collateral_per_ticket: u64,
+    ) -> Result<()> {
+        instructions::initialize_condition_group(ctx, collateral_per_ticket)
+    }
+
+    pub fn add_group_condition(ctx: Context<AddGroupCondition>) -> Result<()> {
+        instructions::add_group_condition(ctx)
+    }
+
+    pub fn convert_no_positions<'info>(
+        ctx: Context<'_, '_, '_, 'info, ConvertNoPositions<'info>>,
+        tickets_amount: u64,
+        keep_index: u64,
+    ) -> Result<()> {
+        instructions::convert_no_positions(ctx, tickets_amount, keep_index)
+    }
+
+    pub fn settle_group<'info>(ctx: Context<'_, '_, '_, 'info, SettleGroup<'info>>) -> Result<()> {
+        instructions::settle_group(ctx)
+    }
+
+    pub fn set_flash_fee(ctx: Context<SetFlashFee>, fee_bps: u64) -> Result<()> {
+        instructions::set_flash_fee(ctx, fee_bps)
+    }
+
+    pub fn flash_mint(ctx: Context<FlashMint>, tickets_amount: u64) -> Result<()> {
+        instructions::flash_mint(ctx, tickets_amount)
+    }
+
+    pub fn flash_repay(ctx: Context<FlashRepay>, sets_to_burn: u64) -> Result<()> {
+        instructions::flash_repay(ctx, sets_to_burn)
+    }
+
+    pub fn get_condition_stats(ctx: Context<GetConditionStats>) -> Result<state::ConditionStats> {
+        instructions::get_condition_stats(ctx)
+    }
 }
 
@@ -1,6 +1,9 @@
 use anchor_lang::prelude::*;
 use anchor_lang::prelude::Pubkey;
 
+use crate::consts::MAX_GROUP_CONDITIONS;
+use crate::error_codes::ErrorCodes;
+
 
 #[account]
 pub struct Condition {
@@ -23,6 +26,28 @@ pub struct Condition {
     pub collateral_vault: Pubkey, // the vault that holds the collateral tokens
 
     pub ended_at_slot: u64, // the slot at which the condition ended, this is only informative and not used in any logic
+
+    pub gate_mode: u64, // 0 means anyone can open positions, 1 requires a merkle allowlist proof, 2 requires an attestation account
+
+    pub allowlist_root: [u8; 32], // merkle root of the allowed wallets, used when gate_mode is 1
+
+    pub attestation_program: Pubkey, // program that issues attestation accounts, used when gate_mode is 2
+
+    pub wallet_cap: u64, // max collateral a single wallet can put into positions, 0 means no cap
+
+    pub global_cap: u64, // max collateral the vault can hold, 0 means no cap
+
+    pub freezable: u64, // 1 when condition_auth_pda is the freeze authority of both outcome tokens
+
+    pub disputed: u64, // 1 while the resolution is contested, outcome token accounts can be frozen and splitting is paused
+
+    pub group: Pubkey, // the condition group this condition belongs to, default pubkey when it's standalone
+
+    pub flash_outstanding: u64, // outcome sets flash minted and not repaid yet, always 0 outside a transaction
+
+    pub flash_fee_bps: u64, // fee on flash minted sets, in basis points of their collateral value
+
+    pub stats: ConditionStats, // lifetime activity counters, informative only
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Copy)]
@@ -35,9 +60,113 @@ pub struct Outcome {
 }
 
 impl Condition {
-    pub const MAX_SIZE: usize = 29 + 254 + 8 + 32 + 32 + 8 + 32 + 32 + 8 + (4 + 2 * (25 + 32 * 8));
+    pub const MAX_SIZE: usize = 29 + 254 + 8 + 32 + 32 + 8 + 32 + 32 + 8 + (4 + 2 * (25 + 32 * 8)) + 8 + 32 + 32 + 8 + 8 + 8 + 8 + 32 + 8 + 8 + ConditionStats::SIZE;
+
+    // gating is active once a mode is set or any cap is configured, wallets then need a registered position
+    pub fn is_gated(&self) -> bool {
+        self.gate_mode != GATE_MODE_OPEN || self.wallet_cap > 0 || self.global_cap > 0
+    }
+
+    pub fn is_grouped(&self) -> bool {
+        self.group != Pubkey::default()
+    }
+}
+
+pub const GATE_MODE_OPEN: u64 = 0;
+pub const GATE_MODE_ALLOWLIST: u64 = 1;
+pub const GATE_MODE_ATTESTATION: u64 = 2;
+
+// tracks how much collateral a registered wallet put into a gated condition
+#[account]
+pub struct WalletPosition {
+    pub condition: Pubkey,
+
+    pub wallet: Pubkey,
+
+    pub collateral: u64, // lifetime collateral counted against the wallet cap, redemptions don't reduce it
+
+    pub unsplit_tickets: u64, // tickets minted by this wallet and not split yet, splitting them doesn't count twice
+}
+
+impl WalletPosition {
+    pub const SIZE: usize = 8 + 32 + 32 + 8 + 8;
+
+    // counts collateral against the wallet cap, cap of 0 means unlimited
+    pub fn add_collateral(&mut self, amount: u64, cap: u64) -> Result<()> {
+        let collateral = self
+            .collateral
+            .checked_add(amount)
+            .ok_or(ErrorCodes::WalletCapExceeded)?;
+        if cap > 0 && collateral > cap {
+            return err!(ErrorCodes::WalletCapExceeded);
+        }
+        self.collateral = collateral;
+        Ok(())
+    }
+}
+
+// every amount is in tickets, multiply by collateral_per_ticket for the collateral value
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Copy)]
+pub struct ConditionStats {
+    pub tickets_minted: u64, // tickets minted against collateral, including flash mints repaid with collateral
+
+    pub tickets_redeemed: u64, // tickets burnt back for their collateral before resolution
+
+    pub tickets_split: u64,
+
+    pub tickets_merged: u64,
+
+    pub payout_redeemed: u64, // winner outcome tokens redeemed after resolution
+
+    pub open_interest: u64, // tickets currently backed by collateral in the vault
+
+    pub peak_open_interest: u64,
+}
+
+impl ConditionStats {
+    pub const SIZE: usize = 7 * 8;
+
+    pub fn add_open_interest(&mut self, tickets_amount: u64) -> Result<()> {
+        self.open_interest = self
+            .open_interest
+            .checked_add(tickets_amount)
+            .ok_or(ErrorCodes::AmountOverflow)?;
+        if self.open_interest > self.peak_open_interest {
+            self.peak_open_interest = self.open_interest;
+        }
+        Ok(())
+    }
+
+    pub fn remove_open_interest(&mut self, tickets_amount: u64) {
+        self.open_interest = self.open_interest.saturating_sub(tickets_amount);
+    }
 }
 
 #[account]
 #[derive(Default)]
 pub struct AuthAccount {}
+
+// in a group outcome 1 is YES and outcome 2 is NO
+pub const YES_OUTCOME: usize = 0;
+pub const NO_OUTCOME: usize = 1;
+
+// links binary conditions of a "who wins" event where at most one of them can resolve YES.
+// members share the collateral token and ticket price so NO positions can be converted between them
+#[account]
+pub struct ConditionGroup {
+    pub authority: Pubkey, // the authority that can add conditions to the group
+
+    pub collateral_token: Pubkey, // the mint of the collateral token every member uses
+
+    pub collateral_per_ticket: u64, // the ticket price every member uses
+
+    pub conditions: Vec<Pubkey>, // member conditions, ordered as they were added
+
+    pub winner: Pubkey, // the member that resolved YES, default pubkey while there's none
+
+    pub settled: u64, // 1 once every member resolved and the vaults got rebalanced, payouts are open after that
+}
+
+impl ConditionGroup {
+    pub const MAX_SIZE: usize = 8 + 32 + 32 + 8 + (4 + 32 * MAX_GROUP_CONDITIONS) + 32 + 8;
+}
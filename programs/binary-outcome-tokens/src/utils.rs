@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+use crate::consts::CONDITION_AUTH_PDA_SEED;
+use crate::error_codes::ErrorCodes;
+use crate::state::Condition;
+
+// checks an account passed in remaining accounts is the auth PDA of the condition and returns its bump
+pub fn condition_auth_bump(condition_key: &Pubkey, condition_auth_pda: &AccountInfo) -> Result<u8> {
+    let (expected, bump) = Pubkey::find_program_address(
+        &[CONDITION_AUTH_PDA_SEED, condition_key.as_ref()],
+        &crate::ID,
+    );
+    if condition_auth_pda.key() != expected {
+        return err!(ErrorCodes::InvalidGroupAccounts);
+    }
+    Ok(bump)
+}
+
+// loads a group member from remaining accounts, making sure it's the expected condition
+pub fn load_group_member<'info>(
+    account: &AccountInfo<'info>,
+    expected: &Pubkey,
+) -> Result<Account<'info, Condition>> {
+    if account.key() != *expected {
+        return err!(ErrorCodes::InvalidGroupAccounts);
+    }
+    Account::try_from(account)
+}
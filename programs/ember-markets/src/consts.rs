@@ -1,9 +1,64 @@
 
-pub const ORDER_BOOK_SIZE: usize = 128;
+pub const ORDER_BOOK_SIZE: usize = 1024;
 
-pub const USERS_BALANCES: usize = 1000;
+// a critbit tree with n leaves has n - 1 inner nodes
+pub const ORDER_BOOK_NODES: usize = 2 * ORDER_BOOK_SIZE;
+
+// order ids keep the price in 32 bits
+pub const MAX_ORDER_PRICE: u64 = u32::MAX as u64;
+
+// fills and outs waiting for the consume_events crank
+pub const EVENT_QUEUE_SIZE: usize = 512;
+
+// fees are in basis points of the quote traded
+pub const BPS_DENOMINATOR: u64 = 10_000;
+pub const MAX_FEE_BPS: u64 = 1_000;
+
+pub const FEE_TIERS_SEED: &[u8] = b"fee_tiers";
+pub const MAX_FEE_TIERS: usize = 10;
+
+// fee tiers look at the volume of the current and the previous period
+pub const VOLUME_PERIOD: u64 = 30 * 24 * 60 * 60;
+
+// liquidity rewards accrue rewards_multiplier reward tokens per REWARDS_PRECISION lot seconds of quoting at
+// the midpoint. a sample never pays for more than MAX_REWARDS_SAMPLE_INTERVAL seconds of a book it didn't see
+pub const REWARDS_PRECISION: u64 = 1_000_000;
+pub const MAX_REWARDS_SAMPLE_INTERVAL: u64 = 10 * 60;
+pub const MIN_REWARDS_MULTIPLIER: u64 = 100;
 
-pub const ADMIN_WALLETS: &'static [&str; 1] = &["5GrCgeZRNtGgKe7ezhSo5vU6ug68JsrC1FCo9246DBgg"];
+pub const REFERRAL_SEED: &[u8] = b"referral";
+
+pub const MAX_QUESTION_LENGTH: usize = 200;
+
+// pda seeds of the mints and vaults create_market_with_condition creates, followed by the market key
+pub const TICKET_MINT_SEED: &[u8] = b"ticket_mint";
+pub const OUTCOME_1_MINT_SEED: &[u8] = b"outcome_1_mint";
+pub const OUTCOME_2_MINT_SEED: &[u8] = b"outcome_2_mint";
+pub const BASE_VAULT_1_SEED: &[u8] = b"base_vault_1";
+pub const BASE_VAULT_2_SEED: &[u8] = b"base_vault_2";
+pub const QUOTE_VAULT_SEED: &[u8] = b"quote_vault";
+pub const TICKET_VAULT_SEED: &[u8] = b"ticket_vault";
+pub const FEE_VAULT_SEED: &[u8] = b"fee_vault";
+pub const BOND_VAULT_SEED: &[u8] = b"bond_vault";
+
+// limits the program config starts with. the bond in native quote tokens market creators without the
+// market creator role post is sized for a 6 decimals stablecoin
+pub const CREATOR_BOND: u64 = 100_000_000;
+pub const MAX_CREATOR_FEE_BPS: u64 = 5_000;
+pub const MIN_MARKET_DURATION: u64 = 60 * 60;
+
+pub const PROGRAM_CONFIG_SEED: &[u8] = b"program_config";
+pub const MAX_ADMINS: usize = 10;
+
+// admin roles, bits of the roles of an admin. ROLE_ADMIN manages the admins and the program config,
+// resolves markets and slashes bonds
+pub const ROLE_ADMIN: u64 = 1 << 0;
+pub const ROLE_MARKET_CREATOR: u64 = 1 << 1; // creates markets without a bond
+pub const ROLE_PAUSER: u64 = 1 << 2;
+pub const ROLE_FEE_ADMIN: u64 = 1 << 3; // fees, fee tiers, referral shares and liquidity rewards
+pub const ALL_ROLES: u64 = ROLE_ADMIN | ROLE_MARKET_CREATOR | ROLE_PAUSER | ROLE_FEE_ADMIN;
+
+pub const USERS_BALANCES: usize = 1000;
 
 pub const USER_ACCOUNT_PDA_SEED: &[u8] = b"user_account_pda_seed";
 
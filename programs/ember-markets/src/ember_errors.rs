@@ -23,5 +23,73 @@ pub enum EmberErr {
     #[msg("The condition account is owned by the wrong program")]
     InvalidConditionOwner,
     #[msg("Provided token doesn't match the corresponding condition token")]
-    InvalidToken
+    InvalidToken,
+    #[msg("Price should be above 0 and below the market's max price")]
+    InvalidPrice,
+    #[msg("An order with the same id is already on the book")]
+    DuplicateOrderId,
+    #[msg("Order not found on the book")]
+    OrderNotFound,
+    #[msg("Post only order can't slide to a valid price")]
+    PostOnlySlideNoPrice,
+    #[msg("Immediate or cancel order didn't fill")]
+    ImmediateOrCancelNotFilled,
+    #[msg("Fill or kill order couldn't be filled entirely")]
+    FillOrKillNotFilled,
+    #[msg("Both orderbooks provided are the same")]
+    SameOrderBook,
+    #[msg("Order would trade against an order of the same user")]
+    SelfTrade,
+    #[msg("Market order filled less than the minimum fill")]
+    MinimumFillNotReached,
+    #[msg("Amount overflows")]
+    AmountOverflow,
+    #[msg("Event queue is full, events need to be consumed first")]
+    EventQueueFull,
+    #[msg("Tick size and lot sizes should be above 0 and give a whole max price")]
+    InvalidLotSizes,
+    #[msg("Fees should be at most the max fee and maker rebates can't exceed the taker fee")]
+    InvalidFees,
+    #[msg("Fee tiers should be sorted by volume, with discounts of at most 100%")]
+    InvalidFeeTiers,
+    #[msg("Accounts passed for the maker of a fill don't match it")]
+    InvalidMakerAccounts,
+    #[msg("Referral share should be at most 100%")]
+    InvalidReferralShare,
+    #[msg("Users can't refer themselves")]
+    SelfReferral,
+    #[msg("Market is closed for trading")]
+    MarketClosed,
+    #[msg("Market is still open until its end time")]
+    MarketNotEnded,
+    #[msg("Condition isn't resolved or its resolution is disputed")]
+    ConditionNotResolved,
+    #[msg("Market isn't resolved yet")]
+    MarketNotResolved,
+    #[msg("Market is already settled")]
+    MarketSettled,
+    #[msg("Market is being settled, balances can be claimed once it's done")]
+    SettlementInProgress,
+    #[msg("Events need to be consumed first")]
+    EventsPending,
+    #[msg("Creator fee should be at most the max creator fee")]
+    InvalidCreatorFee,
+    #[msg("Creating a market requires a bond account")]
+    BondRequired,
+    #[msg("Markets created with a bond have to be resolved through the market")]
+    MarketResolverRequired,
+    #[msg("Bond can only be refunded once the market is settled")]
+    BondNotRefundable,
+    #[msg("Market has no bond")]
+    NoBond,
+    #[msg("Program config can't hold more admins")]
+    TooManyAdmins,
+    #[msg("Admin not found")]
+    AdminNotFound,
+    #[msg("At least one admin has to keep the admin role")]
+    LastAdmin,
+    #[msg("Roles should be a non empty set of known roles")]
+    InvalidRoles,
+    #[msg("Signer is not the upgrade authority of the program")]
+    InvalidUpgradeAuthority,
 }
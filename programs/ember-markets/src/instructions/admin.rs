@@ -1,35 +1,70 @@
-use std::str::FromStr;
-
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, Mint, TokenAccount};
 
-use crate::consts::{ADMIN_WALLETS, MARKET_AUTH_SEED};
+use crate::consts::{
+    BPS_DENOMINATOR, FEE_TIERS_SEED, MARKET_AUTH_SEED, MAX_FEE_BPS, MAX_FEE_TIERS, PROGRAM_CONFIG_SEED,
+    ROLE_FEE_ADMIN, ROLE_MARKET_CREATOR,
+};
 use crate::ember_errors::EmberErr;
+use crate::state::event_queue::EventQueue;
 use crate::state::orderbook::OrderBookState;
-use crate::state::state::{Auth, Market, UsersBalances};
+use crate::state::side::Side;
+use crate::state::state::{creator_share, Auth, FeeTier, FeeTiers, Market, ProgramConfig, UsersBalances};
+use crate::utils::transfer_tokens_signed;
 use binary_outcome_tokens::state::Condition;
 
-pub fn initialize_market(ctx: Context<InitializeMarket>) -> Result<()> {
-    confirm_admin(&ctx.accounts.signer)?;
+pub fn initialize_market(
+    ctx: Context<InitializeMarket>,
+    tick_size: u64,
+    base_lot_size: u64,
+    quote_lot_size: u64,
+    end_time: u64,
+) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_MARKET_CREATOR)?;
 
-    {
-        ctx.accounts.orderbook_state_1.load_init()?;
-        ctx.accounts.orderbook_state_2.load_init()?;
-        ctx.accounts.balances.load_init()?;
-    }
+    ctx.accounts.market.init(
+        &ctx.accounts.config,
+        ctx.accounts.signer.key(),
+        tick_size,
+        base_lot_size,
+        quote_lot_size,
+        end_time,
+    )?;
+    init_market_accounts(
+        &mut ctx.accounts.market,
+        &ctx.accounts.orderbook_state_1,
+        &ctx.accounts.orderbook_state_2,
+        &ctx.accounts.balances,
+        &ctx.accounts.event_queue,
+    )
+}
 
-    ctx.accounts.market.creator = *ctx.accounts.signer.key;
-    ctx.accounts.market.orderbook_state_1 = ctx.accounts.orderbook_state_1.key();
-    ctx.accounts.market.orderbook_state_2 = ctx.accounts.orderbook_state_2.key();
+// initializes the zeroed orderbooks, balances and event queue of a new market
+pub fn init_market_accounts<'info>(
+    market: &mut Market,
+    orderbook_state_1: &AccountLoader<'info, OrderBookState>,
+    orderbook_state_2: &AccountLoader<'info, OrderBookState>,
+    balances: &AccountLoader<'info, UsersBalances>,
+    event_queue: &AccountLoader<'info, EventQueue>,
+) -> Result<()> {
+    for orderbook in [orderbook_state_1, orderbook_state_2] {
+        let orderbook = &mut orderbook.load_init()?;
+        orderbook.bids.init(Side::Bid);
+        orderbook.asks.init(Side::Ask);
+    }
+    balances.load_init()?;
+    event_queue.load_init()?;
 
-    ctx.accounts.market.balances = ctx.accounts.balances.key();
-    ctx.accounts.market.resolved = false;
+    market.orderbook_state_1 = orderbook_state_1.key();
+    market.orderbook_state_2 = orderbook_state_2.key();
+    market.balances = balances.key();
+    market.event_queue = event_queue.key();
     Ok(())
 }
 
 pub fn initialize_vaults(ctx: Context<InitializeVaults>) -> Result<()> {
 
-    confirm_admin(&ctx.accounts.signer)?;
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_MARKET_CREATOR)?;
 
     let condition_struct = &ctx.accounts.condition;
 
@@ -46,6 +81,10 @@ pub fn initialize_vaults(ctx: Context<InitializeVaults>) -> Result<()> {
         ctx.accounts.quote_token.key() == condition_struct.collateral_token,
         EmberErr::InvalidToken
     );
+    require!(
+        ctx.accounts.ticket_token.key() == condition_struct.ticket_token_mint,
+        EmberErr::InvalidToken
+    );
 
     ctx.accounts.market.condition_key = ctx.accounts.condition.key();
     ctx.accounts.market.quote_key = condition_struct.collateral_token.key();
@@ -54,17 +93,104 @@ pub fn initialize_vaults(ctx: Context<InitializeVaults>) -> Result<()> {
     ctx.accounts.market.base_vault_1 = ctx.accounts.base_vault_1.key();
     ctx.accounts.market.base_vault_2 = ctx.accounts.base_vault_2.key();
     ctx.accounts.market.quote_vault = ctx.accounts.quote_vault.key();
+    ctx.accounts.market.ticket_vault = ctx.accounts.ticket_vault.key();
+    ctx.accounts.market.fee_vault = ctx.accounts.fee_vault.key();
+
+    ctx.accounts.market.set_max_price(condition_struct.collateral_per_ticket)
+}
 
+// maker_fee_bps can be negative for a maker rebate, which is paid out of the taker fee so it can't be above it.
+// orders already resting keep the maker fee they were placed with
+pub fn set_market_fees(ctx: Context<SetMarketFees>, maker_fee_bps: i64, taker_fee_bps: u64) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_FEE_ADMIN)?;
+
+    validate_fees(maker_fee_bps, taker_fee_bps)?;
+    ctx.accounts.market.maker_fee_bps = maker_fee_bps;
+    ctx.accounts.market.taker_fee_bps = taker_fee_bps;
+
+    msg!("market fees set, maker {} bps, taker {} bps", maker_fee_bps, taker_fee_bps);
+    Ok(())
+}
+
+pub fn validate_fees(maker_fee_bps: i64, taker_fee_bps: u64) -> Result<()> {
+    if taker_fee_bps > MAX_FEE_BPS
+        || maker_fee_bps.unsigned_abs() > MAX_FEE_BPS
+        || (maker_fee_bps < 0 && maker_fee_bps.unsigned_abs() > taker_fee_bps)
+    {
+        return err!(EmberErr::InvalidFees);
+    }
     Ok(())
 }
 
-pub fn confirm_admin(signer_address: &Signer) -> Result<()> {
-    let market_admin_addresses: Vec<Pubkey> = ADMIN_WALLETS
-        .iter()
-        .map(|address| Pubkey::from_str(address).unwrap())
-        .collect();
+pub fn set_referral_share(ctx: Context<SetMarketFees>, referral_share_bps: u64) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_FEE_ADMIN)?;
+
+    if referral_share_bps > BPS_DENOMINATOR {
+        return err!(EmberErr::InvalidReferralShare);
+    }
+    ctx.accounts.market.referral_share_bps = referral_share_bps;
+
+    msg!("referral share set to {} bps", referral_share_bps);
+    Ok(())
+}
+
+// sends everything accrued in the fee vault to the receiver, but for the creator's share which stays in the
+// vault until the creator claims it
+pub fn withdraw_fees(ctx: Context<WithdrawFees>) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_FEE_ADMIN)?;
+
+    let market = &mut ctx.accounts.market;
+    let accrued = ctx.accounts.fee_vault.amount.saturating_sub(market.creator_fees);
+    let creator_fees = creator_share(accrued, market.creator_fee_bps);
+    market.creator_fees += creator_fees;
+    let amount = accrued - creator_fees;
+
+    let bump = ctx.bumps.get("market_auth_pda").unwrap();
+    let market = ctx.accounts.market.key();
+    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market.as_ref(), &[*bump]]];
+    transfer_tokens_signed(
+        ctx.accounts.market_auth_pda.to_account_info(),
+        ctx.accounts.fee_vault.to_account_info(),
+        ctx.accounts.receiver.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        amount,
+        seeds,
+    )?;
+
+    msg!("withdrew {} in fees, {} to the creator", amount, creator_fees);
+    Ok(())
+}
+
+pub fn initialize_fee_tiers(ctx: Context<InitializeFeeTiers>) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_FEE_ADMIN)?;
+    ctx.accounts.fee_tiers.tiers = Vec::new();
+    Ok(())
+}
+
+// replaces the whole table, tiers have to be sorted by strictly increasing min_volume
+pub fn set_fee_tiers(ctx: Context<SetFeeTiers>, tiers: Vec<FeeTier>) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_FEE_ADMIN)?;
+
+    if tiers.len() > MAX_FEE_TIERS {
+        return err!(EmberErr::InvalidFeeTiers);
+    }
+    for tier in &tiers {
+        if tier.maker_discount_bps > BPS_DENOMINATOR || tier.taker_discount_bps > BPS_DENOMINATOR {
+            return err!(EmberErr::InvalidFeeTiers);
+        }
+    }
+    if tiers.windows(2).any(|pair| pair[0].min_volume >= pair[1].min_volume) {
+        return err!(EmberErr::InvalidFeeTiers);
+    }
+
+    msg!("{} fee tiers set", tiers.len());
+    ctx.accounts.fee_tiers.tiers = tiers;
+    Ok(())
+}
 
-    if !market_admin_addresses.contains(&signer_address.key()) {
+// the signer has to be an admin of the program config with the role
+pub fn confirm_admin(config: &ProgramConfig, signer_address: &Signer, role: u64) -> Result<()> {
+    if !config.has_role(&signer_address.key(), role) {
         return Err(EmberErr::InvalidAdmin.into());
     }
     Ok(())
@@ -75,6 +201,9 @@ pub struct InitializeMarket<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
 
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Box<Account<'info, ProgramConfig>>,
+
     #[account(init, payer = signer, space = Market::SIZE)]
     pub market: Box<Account<'info, Market>>,
 
@@ -84,6 +213,8 @@ pub struct InitializeMarket<'info> {
     pub orderbook_state_2: AccountLoader<'info, OrderBookState>,
     #[account(zero)]
     pub balances: AccountLoader<'info, UsersBalances>,
+    #[account(zero)]
+    pub event_queue: AccountLoader<'info, EventQueue>,
 
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
@@ -93,6 +224,8 @@ pub struct InitializeMarket<'info> {
 pub struct InitializeVaults<'info> {
     #[account(mut, constraint = market.creator == signer.key())]
     signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Box<Account<'info, ProgramConfig>>,
 
     #[account(mut)]
     pub market: Box<Account<'info, Market>>,
@@ -104,6 +237,7 @@ pub struct InitializeVaults<'info> {
     pub base_token_1: Box<Account<'info, Mint>>,
     pub base_token_2: Box<Account<'info, Mint>>,
     pub quote_token: Box<Account<'info, Mint>>,
+    pub ticket_token: Box<Account<'info, Mint>>,
 
     #[account(
         init,
@@ -127,7 +261,68 @@ pub struct InitializeVaults<'info> {
         token::authority = market_auth_pda,
         payer = signer)]
     pub quote_vault: Box<Account<'info, TokenAccount>>,
+
+
+    #[account(
+        init,
+        token::mint = ticket_token,
+        token::authority = market_auth_pda,
+        payer = signer)]
+    pub ticket_vault: Box<Account<'info, TokenAccount>>,
+
+
+    #[account(
+        init,
+        token::mint = quote_token,
+        token::authority = market_auth_pda,
+        payer = signer)]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
     pub rent: Sysvar<'info, Rent>,
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
+
+#[derive(Accounts)]
+pub struct SetMarketFees<'info> {
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    #[account(mut, constraint = fee_vault.key() == market.fee_vault @ EmberErr::InvalidMarket)]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = receiver.mint == market.quote_key @ EmberErr::InvalidToken)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeTiers<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(init, seeds = [FEE_TIERS_SEED], bump, payer = signer, space = FeeTiers::SIZE)]
+    pub fee_tiers: Account<'info, FeeTiers>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeTiers<'info> {
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut, seeds = [FEE_TIERS_SEED], bump)]
+    pub fee_tiers: Account<'info, FeeTiers>,
+}
@@ -0,0 +1,201 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+
+use binary_outcome_tokens::cpi::accounts::{MergeTicket, MintTicket, RedeemPayout, RedeemTicket, SplitTicket};
+use binary_outcome_tokens::program::BinaryOutcomeTokens;
+use binary_outcome_tokens::state::Condition;
+
+use crate::ember_errors::EmberErr;
+use crate::state::side::Side;
+use crate::state::state::Market;
+
+// accounts to mint and merge outcome sets of the market's condition, used when orders on the two
+// outcome books match each other. the market auth pda holds the vaults and signs the cpis
+#[derive(Accounts)]
+pub struct CompleteSet<'info> {
+    #[account(mut)]
+    pub condition: Box<Account<'info, Condition>>,
+    /// CHECK: checked by binary-outcome-tokens
+    #[account(mut)]
+    pub condition_auth_pda: UncheckedAccount<'info>,
+    /// CHECK: checked by binary-outcome-tokens
+    #[account(mut)]
+    pub collateral_vault: UncheckedAccount<'info>,
+    /// CHECK: checked by binary-outcome-tokens
+    #[account(mut)]
+    pub ticket_token_mint: UncheckedAccount<'info>,
+    /// CHECK: checked by binary-outcome-tokens
+    #[account(mut)]
+    pub outcome_1_token: UncheckedAccount<'info>,
+    /// CHECK: checked by binary-outcome-tokens
+    #[account(mut)]
+    pub outcome_2_token: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub ticket_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub base_vault_1: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub base_vault_2: Box<Account<'info, TokenAccount>>,
+
+    pub binary_outcome_tokens_program: Program<'info, BinaryOutcomeTokens>,
+}
+
+impl<'info> CompleteSet<'info> {
+    pub fn validate(&self, market: &Market) -> Result<()> {
+        require!(self.condition.key() == market.condition_key, EmberErr::InvalidMarket);
+        require!(self.ticket_vault.key() == market.ticket_vault, EmberErr::InvalidMarket);
+        require!(self.base_vault_1.key() == market.base_vault_1, EmberErr::InvalidMarket);
+        require!(self.base_vault_2.key() == market.base_vault_2, EmberErr::InvalidMarket);
+        Ok(())
+    }
+
+    // whether orders of this side can be matched across the books. bids need new sets to be minted and
+    // split, which the condition doesn't allow when gated or disputed, asks need sets to be merged and redeemed
+    pub fn can_match(&self, side: Side) -> bool {
+        let condition = &self.condition;
+        match side {
+            Side::Bid => condition.active == 1 && condition.disputed == 0 && !condition.is_gated(),
+            Side::Ask => condition.active == 1,
+        }
+    }
+
+    // sets for the fills an order took from the other outcome book, minted for bids and merged for asks
+    pub fn settle_complement_fills(
+        &self,
+        side: Side,
+        market_auth_pda: AccountInfo<'info>,
+        quote_vault: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        sets: u64,
+        seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        if sets == 0 {
+            return Ok(());
+        }
+        msg!("matched {} sets across the outcome books", sets);
+        match side {
+            Side::Bid => self.mint_sets(market_auth_pda, quote_vault, token_program, sets, seeds),
+            Side::Ask => self.merge_sets(market_auth_pda, quote_vault, token_program, sets, seeds),
+        }
+    }
+
+    // redeems the whole base vault of the winning outcome, 1 or 2, for collateral into the quote vault
+    pub fn redeem_winning_vault(
+        &self,
+        winner: u64,
+        market_auth_pda: AccountInfo<'info>,
+        quote_vault: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        group: Option<AccountInfo<'info>>,
+        seeds: &[&[&[u8]]],
+    ) -> Result<u64> {
+        let (vault, outcome_token) = if winner == 1 {
+            (&self.base_vault_1, &self.outcome_1_token)
+        } else {
+            (&self.base_vault_2, &self.outcome_2_token)
+        };
+        let amount = vault.amount;
+        if amount == 0 {
+            return Ok(0);
+        }
+
+        let cpi_accounts = RedeemPayout {
+            signer: market_auth_pda,
+            condition: self.condition.to_account_info(),
+            condition_auth_pda: self.condition_auth_pda.to_account_info(),
+            outcome_token: outcome_token.to_account_info(),
+            payer: vault.to_account_info(),
+            collateral_vault: self.collateral_vault.to_account_info(),
+            receiver: quote_vault,
+            group,
+            token_program,
+        };
+        let program = self.binary_outcome_tokens_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(program, cpi_accounts, seeds);
+        binary_outcome_tokens::cpi::redeem_payout(cpi_ctx, amount)?;
+        Ok(amount)
+    }
+
+    // mints sets with collateral from the quote vault and splits them into the base vaults
+    fn mint_sets(
+        &self,
+        market_auth_pda: AccountInfo<'info>,
+        quote_vault: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        sets: u64,
+        seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let program = self.binary_outcome_tokens_program.to_account_info();
+
+        let cpi_accounts = MintTicket {
+            signer: market_auth_pda.clone(),
+            condition: self.condition.to_account_info(),
+            payer: quote_vault,
+            receiver: self.ticket_vault.to_account_info(),
+            condition_auth_pda: self.condition_auth_pda.to_account_info(),
+            collateral_vault: self.collateral_vault.to_account_info(),
+            ticket_token_mint: self.ticket_token_mint.to_account_info(),
+            wallet_position: None,
+            token_program: token_program.clone(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(program.clone(), cpi_accounts, seeds);
+        binary_outcome_tokens::cpi::mint_ticket(cpi_ctx, sets)?;
+
+        let cpi_accounts = SplitTicket {
+            signer: market_auth_pda,
+            condition: self.condition.to_account_info(),
+            payer: self.ticket_vault.to_account_info(),
+            receiver_1: self.base_vault_1.to_account_info(),
+            receiver_2: self.base_vault_2.to_account_info(),
+            condition_auth_pda: self.condition_auth_pda.to_account_info(),
+            outcome_1_token: self.outcome_1_token.to_account_info(),
+            outcome_2_token: self.outcome_2_token.to_account_info(),
+            ticket_token_mint: self.ticket_token_mint.to_account_info(),
+            wallet_position: None,
+            token_program,
+        };
+        let cpi_ctx = CpiContext::new_with_signer(program, cpi_accounts, seeds);
+        binary_outcome_tokens::cpi::split_ticket(cpi_ctx, sets)
+    }
+
+    // merges sets out of the base vaults and redeems them for collateral into the quote vault
+    fn merge_sets(
+        &self,
+        market_auth_pda: AccountInfo<'info>,
+        quote_vault: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        sets: u64,
+        seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let program = self.binary_outcome_tokens_program.to_account_info();
+
+        let cpi_accounts = MergeTicket {
+            signer: market_auth_pda.clone(),
+            condition: self.condition.to_account_info(),
+            condition_auth_pda: self.condition_auth_pda.to_account_info(),
+            receiver: self.ticket_vault.to_account_info(),
+            payer_1: self.base_vault_1.to_account_info(),
+            payer_2: self.base_vault_2.to_account_info(),
+            outcome_1_token: self.outcome_1_token.to_account_info(),
+            outcome_2_token: self.outcome_2_token.to_account_info(),
+            ticket_token_mint: self.ticket_token_mint.to_account_info(),
+            token_program: token_program.clone(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(program.clone(), cpi_accounts, seeds);
+        binary_outcome_tokens::cpi::merge_ticket(cpi_ctx, sets)?;
+
+        let cpi_accounts = RedeemTicket {
+            signer: market_auth_pda,
+            condition: self.condition.to_account_info(),
+            payer: self.ticket_vault.to_account_info(),
+            receiver: quote_vault,
+            ticket_token_mint: self.ticket_token_mint.to_account_info(),
+            condition_auth_pda: self.condition_auth_pda.to_account_info(),
+            collateral_vault: self.collateral_vault.to_account_info(),
+            token_program,
+        };
+        let cpi_ctx = CpiContext::new_with_signer(program, cpi_accounts, seeds);
+        binary_outcome_tokens::cpi::redeem_ticket(cpi_ctx, sets)
+    }
+}
@@ -0,0 +1,86 @@
+use anchor_lang::prelude::*;
+
+use crate::consts::{BPS_DENOMINATOR, PROGRAM_CONFIG_SEED, ROLE_ADMIN};
+use crate::ember_errors::EmberErr;
+use crate::instructions::{confirm_admin, validate_fees};
+use crate::program::EmberMarkets;
+use crate::state::state::ProgramConfig;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ProgramConfigParams {
+    pub maker_fee_bps: i64,
+    pub taker_fee_bps: u64,
+    pub referral_share_bps: u64,
+    pub creator_bond: u64,
+    pub max_creator_fee_bps: u64,
+    pub min_market_duration: u64,
+}
+
+// can only be called once, by the upgrade authority of the program which becomes the first admin
+pub fn initialize_program_config(ctx: Context<InitializeProgramConfig>) -> Result<()> {
+    ctx.accounts.config.init(ctx.accounts.signer.key());
+
+    msg!("program config initialized, admin {}", ctx.accounts.signer.key());
+    Ok(())
+}
+
+// fees apply to markets created from now on, existing markets keep theirs
+pub fn set_program_config(ctx: Context<UpdateProgramConfig>, params: ProgramConfigParams) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_ADMIN)?;
+
+    validate_fees(params.maker_fee_bps, params.taker_fee_bps)?;
+    if params.referral_share_bps > BPS_DENOMINATOR {
+        return err!(EmberErr::InvalidReferralShare);
+    }
+    if params.max_creator_fee_bps > BPS_DENOMINATOR {
+        return err!(EmberErr::InvalidCreatorFee);
+    }
+
+    let config = &mut ctx.accounts.config;
+    config.maker_fee_bps = params.maker_fee_bps;
+    config.taker_fee_bps = params.taker_fee_bps;
+    config.referral_share_bps = params.referral_share_bps;
+    config.creator_bond = params.creator_bond;
+    config.max_creator_fee_bps = params.max_creator_fee_bps;
+    config.min_market_duration = params.min_market_duration;
+
+    msg!("program config set");
+    Ok(())
+}
+
+// adds an admin, or replaces the roles of an existing one
+pub fn add_admin(ctx: Context<UpdateProgramConfig>, admin: Pubkey, roles: u64) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_ADMIN)?;
+    ctx.accounts.config.set_admin(admin, roles)?;
+
+    msg!("admin {} set with roles {}", admin, roles);
+    Ok(())
+}
+
+pub fn remove_admin(ctx: Context<UpdateProgramConfig>, admin: Pubkey) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_ADMIN)?;
+    ctx.accounts.config.remove_admin(&admin)?;
+
+    msg!("admin {} removed", admin);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeProgramConfig<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(init, seeds = [PROGRAM_CONFIG_SEED], bump, payer = signer, space = ProgramConfig::SIZE)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ EmberErr::InvalidUpgradeAuthority)]
+    pub program: Program<'info, EmberMarkets>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(signer.key()) @ EmberErr::InvalidUpgradeAuthority)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProgramConfig<'info> {
+    pub signer: Signer<'info>,
+    #[account(mut, seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+}
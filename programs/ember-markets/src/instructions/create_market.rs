@@ -0,0 +1,241 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use binary_outcome_tokens::consts::CONDITION_AUTH_PDA_SEED;
+use binary_outcome_tokens::cpi::accounts::InitializeCondition;
+use binary_outcome_tokens::program::BinaryOutcomeTokens;
+
+use crate::consts::{
+    BASE_VAULT_1_SEED, BASE_VAULT_2_SEED, BOND_VAULT_SEED, FEE_VAULT_SEED, MARKET_AUTH_SEED, OUTCOME_1_MINT_SEED,
+    OUTCOME_2_MINT_SEED, PROGRAM_CONFIG_SEED, QUOTE_VAULT_SEED, ROLE_MARKET_CREATOR, TICKET_MINT_SEED,
+    TICKET_VAULT_SEED,
+};
+use crate::ember_errors::EmberErr;
+use crate::instructions::init_market_accounts;
+use crate::state::event_queue::EventQueue;
+use crate::state::orderbook::OrderBookState;
+use crate::state::state::{Auth, Market, ProgramConfig, UsersBalances};
+use crate::utils::transfer_tokens;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct CreateMarketParams {
+    pub question: String,
+    pub condition_name: String,
+    pub outcome_1_name: String,
+    pub outcome_2_name: String,
+    pub collateral_per_ticket: u64,
+    pub end_time: u64,
+    pub tick_size: u64,
+    pub base_lot_size: u64,
+    pub quote_lot_size: u64,
+    // the market auth pda becomes the condition's resolution authority, it's then resolved through resolve_market
+    pub market_resolves: bool,
+    pub creator_fee_bps: u64,
+}
+
+// creates the condition on binary-outcome-tokens and the market on top of it, replacing initialize_condition,
+// initialize_market and initialize_vaults. the mints and vaults are pdas of the market so they don't need to
+// sign, the orderbooks, balances and event queue are too big to be created by the program and are created
+// zeroed beforehand like for initialize_market.
+// anyone can create a market, signers without the market creator role post the creator bond of the
+// program config from bond_payer into the market's bond vault. their markets are resolved by admins through
+// resolve_market, a creator resolving its own condition could pick the winner and still get the bond back
+pub fn create_market_with_condition(ctx: Context<CreateMarketWithCondition>, params: CreateMarketParams) -> Result<()> {
+    let config = &ctx.accounts.config;
+    if params.creator_fee_bps > config.max_creator_fee_bps {
+        return err!(EmberErr::InvalidCreatorFee);
+    }
+    if !config.has_role(&ctx.accounts.signer.key(), ROLE_MARKET_CREATOR) {
+        if !params.market_resolves {
+            return err!(EmberErr::MarketResolverRequired);
+        }
+        let bond_payer = ctx.accounts.bond_payer.as_ref().ok_or(EmberErr::BondRequired)?;
+        transfer_tokens(
+            ctx.accounts.signer.to_account_info(),
+            bond_payer.to_account_info(),
+            ctx.accounts.bond_vault.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            config.creator_bond,
+        )?;
+        ctx.accounts.market.bond = config.creator_bond;
+    }
+    ctx.accounts.market.bond_vault = ctx.accounts.bond_vault.key();
+    ctx.accounts.market.creator_fee_bps = params.creator_fee_bps;
+
+    let market = &mut ctx.accounts.market;
+    market.init(
+        &ctx.accounts.config,
+        ctx.accounts.signer.key(),
+        params.tick_size,
+        params.base_lot_size,
+        params.quote_lot_size,
+        params.end_time,
+    )?;
+    market.set_question(params.question.clone())?;
+    market.set_max_price(params.collateral_per_ticket)?;
+    init_market_accounts(
+        market,
+        &ctx.accounts.orderbook_state_1,
+        &ctx.accounts.orderbook_state_2,
+        &ctx.accounts.balances,
+        &ctx.accounts.event_queue,
+    )?;
+
+    let resolution_auth = if params.market_resolves {
+        Some(ctx.accounts.market_auth_pda.to_account_info())
+    } else {
+        None
+    };
+    let cpi_accounts = InitializeCondition {
+        signer: ctx.accounts.signer.to_account_info(),
+        condition: ctx.accounts.condition.to_account_info(),
+        condition_auth_pda: ctx.accounts.condition_auth_pda.to_account_info(),
+        ticket_token_mint: ctx.accounts.ticket_token_mint.to_account_info(),
+        outcome_token_1: ctx.accounts.outcome_token_1.to_account_info(),
+        outcome_token_2: ctx.accounts.outcome_token_2.to_account_info(),
+        collateral_token: ctx.accounts.quote_token.to_account_info(),
+        collateral_vault: ctx.accounts.collateral_vault.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+        rent: ctx.accounts.rent.to_account_info(),
+        resolution_auth,
+    };
+    let cpi_ctx = CpiContext::new(ctx.accounts.binary_outcome_tokens_program.to_account_info(), cpi_accounts);
+    binary_outcome_tokens::cpi::initialize_condition(
+        cpi_ctx,
+        params.condition_name,
+        params.question,
+        params.outcome_1_name,
+        params.outcome_2_name,
+        params.collateral_per_ticket,
+    )?;
+
+    let market = &mut ctx.accounts.market;
+    market.condition_key = ctx.accounts.condition.key();
+    market.quote_key = ctx.accounts.quote_token.key();
+    market.outcome_1_key = ctx.accounts.outcome_token_1.key();
+    market.outcome_2_key = ctx.accounts.outcome_token_2.key();
+    market.base_vault_1 = ctx.accounts.base_vault_1.key();
+    market.base_vault_2 = ctx.accounts.base_vault_2.key();
+    market.quote_vault = ctx.accounts.quote_vault.key();
+    market.ticket_vault = ctx.accounts.ticket_vault.key();
+    market.fee_vault = ctx.accounts.fee_vault.key();
+
+    msg!("created market on condition {}", market.condition_key);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CreateMarketWithCondition<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Box<Account<'info, ProgramConfig>>,
+
+    #[account(init, payer = signer, space = Market::SIZE)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(zero)]
+    pub orderbook_state_1: AccountLoader<'info, OrderBookState>,
+    #[account(zero)]
+    pub orderbook_state_2: AccountLoader<'info, OrderBookState>,
+    #[account(zero)]
+    pub balances: AccountLoader<'info, UsersBalances>,
+    #[account(zero)]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+    #[account(init, seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump, payer = signer, space = 9)]
+    pub market_auth_pda: Box<Account<'info, Auth>>,
+
+    // initialized by binary-outcome-tokens, like the collateral vault
+    #[account(mut)]
+    pub condition: Signer<'info>,
+    /// CHECK: initialized by binary-outcome-tokens, the seeds make it the mint authority of the outcome tokens
+    #[account(mut, seeds = [CONDITION_AUTH_PDA_SEED, condition.key().as_ref()], bump, seeds::program = binary_outcome_tokens_program.key())]
+    pub condition_auth_pda: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub collateral_vault: Signer<'info>,
+
+    #[account(
+        init,
+        seeds = [TICKET_MINT_SEED, market.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = condition_auth_pda,
+        payer = signer)]
+    pub ticket_token_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        seeds = [OUTCOME_1_MINT_SEED, market.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = condition_auth_pda,
+        mint::freeze_authority = condition_auth_pda,
+        payer = signer)]
+    pub outcome_token_1: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        seeds = [OUTCOME_2_MINT_SEED, market.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = condition_auth_pda,
+        mint::freeze_authority = condition_auth_pda,
+        payer = signer)]
+    pub outcome_token_2: Box<Account<'info, Mint>>,
+    pub quote_token: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        seeds = [BASE_VAULT_1_SEED, market.key().as_ref()],
+        bump,
+        token::mint = outcome_token_1,
+        token::authority = market_auth_pda,
+        payer = signer)]
+    pub base_vault_1: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        seeds = [BASE_VAULT_2_SEED, market.key().as_ref()],
+        bump,
+        token::mint = outcome_token_2,
+        token::authority = market_auth_pda,
+        payer = signer)]
+    pub base_vault_2: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        seeds = [QUOTE_VAULT_SEED, market.key().as_ref()],
+        bump,
+        token::mint = quote_token,
+        token::authority = market_auth_pda,
+        payer = signer)]
+    pub quote_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        seeds = [TICKET_VAULT_SEED, market.key().as_ref()],
+        bump,
+        token::mint = ticket_token_mint,
+        token::authority = market_auth_pda,
+        payer = signer)]
+    pub ticket_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        seeds = [FEE_VAULT_SEED, market.key().as_ref()],
+        bump,
+        token::mint = quote_token,
+        token::authority = market_auth_pda,
+        payer = signer)]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        seeds = [BOND_VAULT_SEED, market.key().as_ref()],
+        bump,
+        token::mint = quote_token,
+        token::authority = market_auth_pda,
+        payer = signer)]
+    pub bond_vault: Box<Account<'info, TokenAccount>>,
+    // quote account the bond is paid from, only needed when the signer doesn't have the market creator role
+    #[account(mut, constraint = bond_payer.mint == quote_token.key() @ EmberErr::InvalidToken)]
+    pub bond_payer: Option<Box<Account<'info, TokenAccount>>>,
+
+    pub binary_outcome_tokens_program: Program<'info, BinaryOutcomeTokens>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
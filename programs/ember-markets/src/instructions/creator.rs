@@ -0,0 +1,135 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::{MARKET_AUTH_SEED, PROGRAM_CONFIG_SEED, ROLE_ADMIN};
+use crate::ember_errors::EmberErr;
+use crate::instructions::confirm_admin;
+use crate::state::market_status::MarketStatus;
+use crate::state::state::{Auth, Market, ProgramConfig};
+use crate::utils::transfer_tokens_signed;
+
+// permissionless, gives the bond back to the creator once the market settled, which takes an undisputed resolution
+pub fn refund_creator_bond(ctx: Context<RefundCreatorBond>) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+    if !market.settled {
+        return err!(EmberErr::BondNotRefundable);
+    }
+    if market.bond == 0 {
+        return err!(EmberErr::NoBond);
+    }
+    let amount = market.bond;
+    market.bond = 0;
+
+    let bump = ctx.bumps.get("market_auth_pda").unwrap();
+    let market = market.key();
+    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market.as_ref(), &[*bump]]];
+    transfer_tokens_signed(
+        ctx.accounts.market_auth_pda.to_account_info(),
+        ctx.accounts.bond_vault.to_account_info(),
+        ctx.accounts.receiver.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        amount,
+        seeds,
+    )?;
+
+    msg!("refunded the creator bond of {}", amount);
+    Ok(())
+}
+
+// for spam or invalid questions, the bond goes to the receiver and an open market stops trading
+pub fn slash_creator_bond(ctx: Context<SlashCreatorBond>) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_ADMIN)?;
+
+    let market = &mut ctx.accounts.market;
+    if market.bond == 0 {
+        return err!(EmberErr::NoBond);
+    }
+    let amount = market.bond;
+    market.bond = 0;
+    if market.status == MarketStatus::Open {
+        market.status = MarketStatus::Closed;
+    }
+
+    let bump = ctx.bumps.get("market_auth_pda").unwrap();
+    let market = market.key();
+    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market.as_ref(), &[*bump]]];
+    transfer_tokens_signed(
+        ctx.accounts.market_auth_pda.to_account_info(),
+        ctx.accounts.bond_vault.to_account_info(),
+        ctx.accounts.receiver.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        amount,
+        seeds,
+    )?;
+
+    msg!("slashed the creator bond of {}", amount);
+    Ok(())
+}
+
+// the creator's share is set aside in the fee vault by withdraw_fees
+pub fn claim_creator_fees(ctx: Context<ClaimCreatorFees>) -> Result<()> {
+    let amount = ctx.accounts.market.creator_fees;
+    ctx.accounts.market.creator_fees = 0;
+
+    let bump = ctx.bumps.get("market_auth_pda").unwrap();
+    let market = ctx.accounts.market.key();
+    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market.as_ref(), &[*bump]]];
+    transfer_tokens_signed(
+        ctx.accounts.market_auth_pda.to_account_info(),
+        ctx.accounts.fee_vault.to_account_info(),
+        ctx.accounts.receiver.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        amount,
+        seeds,
+    )?;
+
+    msg!("claimed {} in creator fees", amount);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RefundCreatorBond<'info> {
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    #[account(mut, constraint = bond_vault.key() == market.bond_vault @ EmberErr::InvalidMarket)]
+    pub bond_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = receiver.mint == market.quote_key @ EmberErr::InvalidToken,
+        constraint = receiver.owner == market.creator @ EmberErr::InvalidToken)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SlashCreatorBond<'info> {
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    #[account(mut, constraint = bond_vault.key() == market.bond_vault @ EmberErr::InvalidMarket)]
+    pub bond_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = receiver.mint == market.quote_key @ EmberErr::InvalidToken)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimCreatorFees<'info> {
+    #[account(constraint = signer.key() == market.creator)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    #[account(mut, constraint = fee_vault.key() == market.fee_vault @ EmberErr::InvalidMarket)]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = receiver.mint == market.quote_key @ EmberErr::InvalidToken)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
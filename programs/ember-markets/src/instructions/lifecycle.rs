@@ -0,0 +1,236 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::{MARKET_AUTH_SEED, PROGRAM_CONFIG_SEED, ROLE_ADMIN, ROLE_PAUSER};
+use crate::ember_errors::EmberErr;
+use crate::instructions::complete_set::*;
+use crate::state::event_queue::EventQueue;
+use crate::state::market_status::MarketStatus;
+use crate::state::orderbook::OrderBookState;
+use crate::state::side::Side;
+use crate::state::state::{Auth, Market, ProgramConfig, UsersBalances};
+use crate::instructions::confirm_admin;
+use binary_outcome_tokens::cpi::accounts::ResolveCondition;
+use binary_outcome_tokens::program::BinaryOutcomeTokens;
+use binary_outcome_tokens::state::Condition;
+
+// permissionless, stops trading once the market reached its end time
+pub fn close_market(ctx: Context<CloseMarket>) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+    if market.status != MarketStatus::Open {
+        return err!(EmberErr::MarketClosed);
+    }
+    let now = Clock::get()?.unix_timestamp as u64;
+    if now < market.end_time {
+        return err!(EmberErr::MarketNotEnded);
+    }
+    market.status = MarketStatus::Closed;
+
+    msg!("market closed");
+    Ok(())
+}
+
+// stops or resumes trading on an open market, resting orders stay on the books
+pub fn set_market_paused(ctx: Context<SetMarketPaused>, paused: bool) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_PAUSER)?;
+    ctx.accounts.market.paused = paused;
+
+    msg!("market paused: {}", paused);
+    Ok(())
+}
+
+// permissionless, marks the market resolved with the winner of its condition. a condition can be resolved
+// before the market's end time, and again after a dispute, so this can be called from any status until
+// the market is settled
+pub fn sync_resolution(ctx: Context<SyncResolution>) -> Result<()> {
+    resolve_from_condition(&mut ctx.accounts.market, &ctx.accounts.condition)
+}
+
+// resolves the condition of a market whose market auth pda is its resolution authority, outcome is the
+// condition's outcome index, 0 or 1
+pub fn resolve_market(ctx: Context<ResolveMarket>, outcome: u64) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_ADMIN)?;
+
+    let bump = ctx.bumps.get("market_auth_pda").unwrap();
+    let market_key = ctx.accounts.market.key();
+    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market_key.as_ref(), &[*bump]]];
+    let cpi_accounts = ResolveCondition {
+        signer: ctx.accounts.market_auth_pda.to_account_info(),
+        condition: ctx.accounts.condition.to_account_info(),
+        condition_auth_pda: ctx.accounts.condition_auth_pda.to_account_info(),
+        group: ctx.accounts.group.as_ref().map(|group| group.to_account_info()),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.binary_outcome_tokens_program.to_account_info(),
+        cpi_accounts,
+        seeds,
+    );
+    binary_outcome_tokens::cpi::resolve_condition(cpi_ctx, outcome)?;
+
+    ctx.accounts.condition.reload()?;
+    resolve_from_condition(&mut ctx.accounts.market, &ctx.accounts.condition)
+}
+
+fn resolve_from_condition(market: &mut Market, condition: &Condition) -> Result<()> {
+    if condition.active != 0 || condition.disputed != 0 {
+        return err!(EmberErr::ConditionNotResolved);
+    }
+    let winner = condition
+        .outcomes
+        .iter()
+        .position(|outcome| outcome.winner == 1)
+        .ok_or(EmberErr::ConditionNotResolved)?;
+
+    // users already settled got paid for the synced winner
+    if market.settled || market.settled_users > 0 {
+        return err!(EmberErr::MarketSettled);
+    }
+    market.status = MarketStatus::Resolved;
+    market.resolved = true;
+    market.winning_outcome = winner as u64 + 1;
+
+    msg!("market resolved, outcome {} won", market.winning_outcome);
+    Ok(())
+}
+
+// permissionless crank once the market is resolved. cancels and refunds the resting orders, once both books
+// are empty it redeems the winning base vault and turns the users' winning balances into quote, so users only
+// claim collateral. a call handles up to limit orders and users, events have to be consumed before
+pub fn settle_market(ctx: Context<SettleMarket>, limit: u64) -> Result<()> {
+    let market = &ctx.accounts.market;
+    if market.status != MarketStatus::Resolved {
+        return err!(EmberErr::MarketNotResolved);
+    }
+    if market.settled {
+        return err!(EmberErr::MarketSettled);
+    }
+    ctx.accounts.complete_set.validate(market)?;
+    // a dispute after sync_resolution can change the winner, it has to be synced again
+    let condition = &ctx.accounts.complete_set.condition;
+    if condition.active != 0
+        || condition.disputed != 0
+        || condition.outcomes[market.winning_outcome as usize - 1].winner != 1
+    {
+        return err!(EmberErr::ConditionNotResolved);
+    }
+    if ctx.accounts.event_queue.load()?.count > 0 {
+        return err!(EmberErr::EventsPending);
+    }
+
+    let balances = &mut ctx.accounts.balances.load_mut()?;
+    let mut cancelled = 0;
+    let mut remaining = 0;
+    for orderbook in [&ctx.accounts.orderbook_state_1, &ctx.accounts.orderbook_state_2] {
+        let base_token = market.base_token(orderbook.key())?;
+        let orderbook = &mut *orderbook.load_mut()?;
+        for (side, book) in [(Side::Bid, &mut orderbook.bids), (Side::Ask, &mut orderbook.asks)] {
+            while cancelled < limit {
+                let order_id = match book.best_order() {
+                    Some((order_id, _)) => order_id,
+                    None => break,
+                };
+                if let Some(order) = book.remove_order(order_id) {
+                    balances.refund_order(&order, side, base_token, market.tick_size)?;
+                }
+                cancelled += 1;
+            }
+            remaining += book.leaf_count;
+        }
+    }
+    // users are settled from a call with limit left, so every call past the redemption moves the cursor
+    if remaining > 0 || cancelled == limit {
+        msg!("cancelled {} orders, {} left", cancelled, remaining);
+        return Ok(());
+    }
+
+    // the vault is redeemed once, before the first user gets settled
+    if market.settled_users == 0 {
+        let bump = ctx.bumps.get("market_auth_pda").unwrap();
+        let market_key = market.key();
+        let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market_key.as_ref(), &[*bump]]];
+        let redeemed = ctx.accounts.complete_set.redeem_winning_vault(
+            market.winning_outcome,
+            ctx.accounts.market_auth_pda.to_account_info(),
+            ctx.accounts.quote_vault.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.group.as_ref().map(|group| group.to_account_info()),
+            seeds,
+        )?;
+        msg!("redeemed {} winning tokens", redeemed);
+    }
+
+    let market = &mut ctx.accounts.market;
+    let last_uid = std::cmp::min(balances.idx, market.settled_users + (limit - cancelled));
+    for uid in market.settled_users + 1..=last_uid {
+        balances.settle_user(uid, market.winning_outcome as u8, market.max_price, market.tick_size)?;
+    }
+    market.settled_users = last_uid;
+    market.settled = last_uid == balances.idx;
+
+    msg!("cancelled {} orders, settled users up to {} of {}", cancelled, last_uid, balances.idx);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CloseMarket<'info> {
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+}
+
+#[derive(Accounts)]
+pub struct SetMarketPaused<'info> {
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+}
+
+#[derive(Accounts)]
+pub struct SyncResolution<'info> {
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(constraint = condition.key() == market.condition_key @ EmberErr::InvalidMarket)]
+    pub condition: Box<Account<'info, Condition>>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveMarket<'info> {
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    #[account(mut, constraint = condition.key() == market.condition_key @ EmberErr::InvalidMarket)]
+    pub condition: Box<Account<'info, Condition>>,
+    /// CHECK: checked by binary-outcome-tokens
+    pub condition_auth_pda: UncheckedAccount<'info>,
+    /// CHECK: checked by binary-outcome-tokens, only needed when the condition belongs to a group
+    #[account(mut)]
+    pub group: Option<UncheckedAccount<'info>>,
+    pub binary_outcome_tokens_program: Program<'info, BinaryOutcomeTokens>,
+}
+
+#[derive(Accounts)]
+pub struct SettleMarket<'info> {
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(mut, constraint = orderbook_state_1.key() == market.orderbook_state_1)]
+    pub orderbook_state_1: AccountLoader<'info, OrderBookState>,
+    #[account(mut, constraint = orderbook_state_2.key() == market.orderbook_state_2)]
+    pub orderbook_state_2: AccountLoader<'info, OrderBookState>,
+    #[account(mut, constraint = balances.key() == market.balances)]
+    pub balances: AccountLoader<'info, UsersBalances>,
+    #[account(constraint = event_queue.key() == market.event_queue)]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+    #[account(mut, seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    #[account(mut, constraint = quote_vault.key() == market.quote_vault @ EmberErr::InvalidMarket)]
+    pub quote_vault: Box<Account<'info, TokenAccount>>,
+    pub complete_set: CompleteSet<'info>,
+    /// CHECK: checked by binary-outcome-tokens, only needed when the condition belongs to a group
+    pub group: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+}
@@ -1,7 +1,18 @@
 mod orderbook;
 mod admin;
+mod complete_set;
 mod user;
+mod rewards;
+mod lifecycle;
+mod create_market;
+mod creator;
+mod config;
 
 pub use admin::*;
 pub use user::*;
-pub use orderbook::*;
\ No newline at end of file
+pub use orderbook::*;
+pub use rewards::*;
+pub use lifecycle::*;
+pub use create_market::*;
+pub use creator::*;
+pub use config::*;
\ No newline at end of file
@@ -1,256 +1,581 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount};
 
-use crate::consts::{MARKET_AUTH_SEED, USER_ACCOUNT_PDA_SEED};
+use crate::consts::{FEE_TIERS_SEED, MARKET_AUTH_SEED, REFERRAL_SEED, USER_ACCOUNT_PDA_SEED};
 use crate::ember_errors::EmberErr;
-use crate::state::orderbook::OrderBookState;
+use crate::instructions::complete_set::*;
+use crate::state::event_queue::{Event, EventQueue, EVENT_FILL};
+use crate::state::order_type::{OrderType, SelfTradeBehavior};
+use crate::state::orderbook::{Complement, FillResult, MarketOrderResult, Order, OrderBookState, TakerOrder};
 use crate::state::side::Side;
-use crate::state::state::{Auth, Market, MarketSpecificUser, User, UsersBalances};
+use crate::state::state::{
+    maker_fee_lock, quote_lots, referral_share, Auth, FeeTiers, Market, MarketSpecificUser, ReferralFees, User,
+    UsersBalances,
+};
 use crate::utils::{transfer_tokens, transfer_tokens_signed};
 
-pub fn place_limit_order(
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct LimitOrderParams {
+    pub side: Side,
+    pub price: u64,
+    pub size: u64,
+    pub expire_in: u64,
+    pub order_type: OrderType,
+    pub client_order_id: u64,
+    pub self_trade_behavior: SelfTradeBehavior,
+}
+
+pub fn place_limit_order(ctx: Context<PlaceLimitOrder>, params: LimitOrderParams) -> Result<()> {
+    place_order(ctx, params, false)
+}
+
+// an ask on the outcome of the orderbook from a user holding only quote. selling the outcome short at
+// price is buying the other outcome at max_price - price, so the order goes to the other book as a bid
+// locking max_price - price of quote. when it fills against a bid on this book an outcome set is minted
+// with both payments, the buyer gets this outcome and the seller the other one. the order is managed as
+// that bid afterwards, cancelling it takes the other orderbook and Side::Bid
+pub fn place_synthetic_ask(
     ctx: Context<PlaceLimitOrder>,
-    side: Side,
     price: u64,
     size: u64,
     expire_in: u64,
+    order_type: OrderType,
+    client_order_id: u64,
+    self_trade_behavior: SelfTradeBehavior,
 ) -> Result<()> {
-    let orderbook = &mut ctx.accounts.orderbook.load_mut()?;
-    // check other side for better price:
-    // TODO: should be improved to enable fills if prices crosses the spread, but a feature for another day.
+    ctx.accounts.market.validate_price(price)?;
+    let params = LimitOrderParams {
+        side: Side::Bid,
+        price: ctx.accounts.market.max_price - price,
+        size,
+        expire_in,
+        order_type,
+        client_order_id,
+        self_trade_behavior,
+    };
+    place_order(ctx, params, true)
+}
+
+// places the order on the orderbook, or on the other orderbook when on_other_book is set
+fn place_order(ctx: Context<PlaceLimitOrder>, params: LimitOrderParams, on_other_book: bool) -> Result<()> {
+    let LimitOrderParams {
+        side,
+        price,
+        size,
+        expire_in,
+        order_type,
+        client_order_id,
+        self_trade_behavior,
+    } = params;
+    let (orderbook, other_orderbook) = if on_other_book {
+        (&ctx.accounts.other_orderbook, &ctx.accounts.orderbook)
+    } else {
+        (&ctx.accounts.orderbook, &ctx.accounts.other_orderbook)
+    };
+    let market = &ctx.accounts.market;
+    market.require_open()?;
+    let base_token = market.base_token(orderbook.key())?;
+    let other_base_token = market.base_token(other_orderbook.key())?;
+    let tick_size = market.tick_size;
+    ctx.accounts.complete_set.validate(market)?;
+    let orderbook = &mut orderbook.load_mut()?;
+    let other_orderbook = &mut *other_orderbook.load_mut()?;
+    let balances = &mut ctx.accounts.balances.load_mut()?;
+    let event_queue = &mut ctx.accounts.event_queue.load_mut()?;
+    let uid = ctx.accounts.user_market_pda.uid;
+    let (maker_fee_bps, taker_fee_bps) = user_fees(market, &ctx.accounts.fee_tiers, &ctx.accounts.user_account)?;
+
+    let complement = complement(
+        &ctx.accounts.complete_set,
+        market,
+        other_orderbook,
+        other_base_token,
+        side,
+    );
+
+    // size is in base lots and price in ticks, a slid post only price gets checked as well
+    let price = orderbook.post_only_price(side, price, order_type, complement.as_ref())?;
+    market.validate_price(price)?;
+
+    // the funds for the whole order are locked first, fills settle out of them
     match side {
-        Side::Bid => {
-            let best_price = orderbook.asks.orders[orderbook.asks.best_order_idx as usize].price;
-            if price > best_price {
-                return err!(EmberErr::PriceCrossesTheSpread);
-            }
-        }
-        Side::Ask => {
-            let best_price = orderbook.bids.orders[orderbook.bids.best_order_idx as usize].price;
-            if price < best_price {
-                return err!(EmberErr::PriceCrossesTheSpread);
-            }
-        }
+        Side::Bid => balances.debt_account(uid, quote_lots(size, price, tick_size)?, 0)?,
+        Side::Ask => balances.debt_account(uid, size, base_token)?,
     }
 
-    let uid = ctx.accounts.user_market_pda.uid;
-    // market is going to be outcome token / usdc denominated, so no need to implement limit orders.
-    // with price, each 1 is worth is 0.01 cents (100 usdc lots), and the base token got 0 decimals so not divisible
-    let total_cost = match side {
-        Side::Bid => {
-            orderbook.bids.insert_order(size, price, uid, expire_in)?;
-            price * size
-        }
-        Side::Ask => {
-            orderbook.asks.insert_order(size, price, uid, expire_in)?;
-            size
-        }
+    // a price crossing the spread fills against the resting orders first, only the remainder rests on the book
+    let fill = if order_type.can_take() {
+        let taker = TakerOrder {
+            side,
+            limit_price: price,
+            size,
+            max_quote: u64::MAX,
+            uid,
+            self_trade_behavior,
+            taker_fee_bps,
+        };
+        orderbook.match_order(&taker, event_queue, base_token, tick_size, complement)?
+    } else {
+        FillResult::default()
     };
+    if order_type == OrderType::FillOrKill && fill.filled < size {
+        return err!(EmberErr::FillOrKillNotFilled);
+    }
+    if order_type == OrderType::ImmediateOrCancel && fill.filled == 0 {
+        return err!(EmberErr::ImmediateOrCancelNotFilled);
+    }
 
-    let market = &ctx.accounts.market;
-    let balances = &mut ctx.accounts.balances.load_mut()?;
     match side {
         Side::Bid => {
-            balances.debt_account(uid, total_cost, 0)?;
+            balances.credit_account(uid, fill.filled, base_token)?;
+            // fills happen at the makers' prices, the price improvement goes back to the taker
+            balances.credit_account(uid, quote_lots(fill.filled, price, tick_size)? - fill.quote_amount, 0)?;
+            balances.debt_account(uid, fill.taker_fee, 0)?;
         }
-        Side::Ask => {
-            if orderbook.base_mint == market.outcome_1_key {
-                balances.debt_account(uid, size, 1)?;
-            } else if orderbook.base_mint == market.outcome_2_key {
-                balances.debt_account(uid, size, 2)?;
-            } else {
-                return err!(EmberErr::InvalidMarket);
-            }
+        Side::Ask => balances.credit_account(uid, fill.quote_amount - fill.taker_fee, 0)?,
+    }
+
+    // the size removed by self trade prevention is unlocked, so is the unfilled part of an
+    // immediate order or of an order cancelled by self trade prevention
+    let mut remaining = size - fill.filled - fill.decremented;
+    let mut unlocked = fill.decremented;
+    if !order_type.can_rest() || fill.taker_cancelled {
+        unlocked += remaining;
+        remaining = 0;
+    }
+    match side {
+        Side::Bid => balances.credit_account(uid, quote_lots(unlocked, price, tick_size)?, 0)?,
+        Side::Ask => balances.credit_account(uid, unlocked, base_token)?,
+    }
+
+    if remaining > 0 {
+        // a resting bid locks its maker fee as well, the order keeps track of what's left of the lock
+        let fee_locked = match side {
+            Side::Bid => maker_fee_lock(quote_lots(remaining, price, tick_size)?, maker_fee_bps),
+            Side::Ask => 0,
+        };
+        balances.debt_account(uid, fee_locked, 0)?;
+        let book = match side {
+            Side::Bid => &mut orderbook.bids,
+            Side::Ask => &mut orderbook.asks,
+        };
+        let order = Order {
+            price,
+            size: remaining,
+            uid,
+            client_order_id,
+            maker_fee_bps,
+            fee_locked,
+            ..Order::default()
+        };
+        let (order_id, evicted) = book.insert_order(order, expire_in)?;
+        if let Some((evicted_id, evicted)) = evicted {
+            event_queue.push(Event::out(side, evicted_id, &evicted, uid, base_token)?)?;
         }
+        msg!("placed order {}", order_id);
     }
 
+    // fills against the other outcome book are backed by sets minted or merged between the vaults
+    let bump = ctx.bumps.get("market_auth_pda").unwrap();
+    let market_key = market.key();
+    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market_key.as_ref(), &[*bump]]];
+    ctx.accounts.complete_set.settle_complement_fills(
+        side,
+        ctx.accounts.market_auth_pda.to_account_info(),
+        ctx.accounts.quote_vault.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        market.base_lots_to_native(fill.complement_filled)?,
+        seeds,
+    )?;
+
+    let volume = market.quote_lots_to_native(fill.quote_amount)?;
+    ctx.accounts.user_account.add_volume(volume, Clock::get()?.unix_timestamp as u64);
+    let user_market_pda = &mut ctx.accounts.user_market_pda;
+    user_market_pda.volume = user_market_pda.volume.saturating_add(volume);
+
+    // the fees stayed in the quote vault with the balances, they move to the fee vault but for the referrer's share
+    let fees = market.quote_lots_to_native(fill.fees)? - credit_referrer(market, &mut ctx.accounts.referral, &fill, volume)?;
+    if fees > 0 {
+        transfer_tokens_signed(
+            ctx.accounts.market_auth_pda.to_account_info(),
+            ctx.accounts.quote_vault.to_account_info(),
+            ctx.accounts.fee_vault.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            fees,
+            seeds,
+        )?;
+    }
+
+    msg!(
+        "filled {} at {} quote and {} taker fee, {} resting on the book",
+        fill.filled,
+        fill.quote_amount,
+        fill.taker_fee,
+        remaining
+    );
     Ok(())
 }
 
+// maker and taker fees of the user, discounted by the fee tier of its trailing volume
+fn user_fees(market: &Market, fee_tiers: &Option<Account<FeeTiers>>, user_account: &User) -> Result<(i64, u64)> {
+    let volume = user_account.trailing_volume(Clock::get()?.unix_timestamp as u64);
+    Ok(FeeTiers::user_fees(fee_tiers.as_deref(), market, volume))
+}
+
+// credits the referrer of the taker with its share of the taker fee, at most the net fees of the fill
+// since rebates are paid out of the taker fee. returns the native amount kept in the quote vault for it.
+// without the referrer's referral account on the market the share goes to the fee vault with the rest
+fn credit_referrer(
+    market: &Market,
+    referral: &mut Option<Account<ReferralFees>>,
+    fill: &FillResult,
+    volume: u64,
+) -> Result<u64> {
+    let referral = match referral {
+        Some(referral) => referral,
+        None => return Ok(0),
+    };
+    let taker_fee = market.quote_lots_to_native(fill.taker_fee)?;
+    let share = std::cmp::min(
+        referral_share(taker_fee, market.referral_share_bps),
+        market.quote_lots_to_native(fill.fees)?,
+    );
+    referral.fees = referral.fees.checked_add(share).ok_or(EmberErr::AmountOverflow)?;
+    referral.total_fees = referral.total_fees.checked_add(share).ok_or(EmberErr::AmountOverflow)?;
+    referral.referred_volume = referral.referred_volume.saturating_add(volume);
+    Ok(share)
+}
+
+// the other outcome book as a complement for an order of the given side, when the condition
+// lets the outcome sets be minted or merged
+fn complement<'a>(
+    complete_set: &CompleteSet,
+    market: &Market,
+    other_orderbook: &'a mut OrderBookState,
+    other_base_token: u8,
+    side: Side,
+) -> Option<Complement<'a>> {
+    if !complete_set.can_match(side) {
+        return None;
+    }
+    let book = match side {
+        Side::Bid => &mut other_orderbook.bids,
+        Side::Ask => &mut other_orderbook.asks,
+    };
+    Some(Complement {
+        book,
+        base_token: other_base_token,
+        max_price: market.max_price,
+    })
+}
+
 pub fn cancel_limit_order(
     ctx: Context<CancelLimitOrder>,
     side: Side,
-    order_idx: u64,
+    order_id: u64,
 ) -> Result<()> {
+    let base_token = ctx.accounts.market.base_token(ctx.accounts.orderbook.key())?;
     let orderbook = &mut ctx.accounts.orderbook.load_mut()?;
     let balances = &mut ctx.accounts.balances.load_mut()?;
-    let orderbook_base_mint = orderbook.base_mint;
 
     let uid = ctx.accounts.user_market_pda.uid;
-    match side {
-        Side::Bid => {
-            let order = &mut orderbook.bids.orders[order_idx as usize];
-            if order.uid != uid {
-                return err!(EmberErr::UnauthorizedOrderCancellation);
-            }
-            balances.credit_account(uid, order.size * order.price, 0)?;
-            orderbook.bids.remove_order(order_idx);
-        }
-        Side::Ask => {
-            let order = &mut orderbook.asks.orders[order_idx as usize];
-            if order.uid != uid {
-                return err!(EmberErr::UnauthorizedOrderCancellation);
-            }
-            if orderbook_base_mint == ctx.accounts.market.outcome_1_key {
-                balances.credit_account(uid, order.size, 1)?;
-            } else if orderbook_base_mint == ctx.accounts.market.outcome_2_key {
-                balances.credit_account(uid, order.size, 2)?;
-            } else {
-                return err!(EmberErr::InvalidMarket);
-            }
-            orderbook.asks.remove_order(order_idx);
-        }
+    let book = match side {
+        Side::Bid => &mut orderbook.bids,
+        Side::Ask => &mut orderbook.asks,
+    };
+    let order_idx = book.find_order(order_id).ok_or(EmberErr::OrderNotFound)?;
+    let order = book.nodes[order_idx as usize].order;
+    if order.uid != uid {
+        return err!(EmberErr::UnauthorizedOrderCancellation);
     }
+    balances.refund_order(&order, side, base_token, ctx.accounts.market.tick_size)?;
+    book.remove_order(order_id);
 
     Ok(())
 }
 
-pub fn place_market_order(ctx: Context<PlaceMarketOrder>, side: Side, amount: u64) -> Result<()> {
+pub fn cancel_order_by_client_id(
+    ctx: Context<CancelLimitOrder>,
+    side: Side,
+    client_order_id: u64,
+) -> Result<()> {
+    let base_token = ctx.accounts.market.base_token(ctx.accounts.orderbook.key())?;
     let orderbook = &mut ctx.accounts.orderbook.load_mut()?;
-    let mut filled_amount = 0;
-    let mut total_cost = 0;
+    let balances = &mut ctx.accounts.balances.load_mut()?;
 
+    let uid = ctx.accounts.user_market_pda.uid;
+    let book = match side {
+        Side::Bid => &mut orderbook.bids,
+        Side::Ask => &mut orderbook.asks,
+    };
+    let cancelled = book.remove_user_orders(uid, Some(client_order_id));
+    if cancelled.is_empty() {
+        return err!(EmberErr::OrderNotFound);
+    }
+    for order in cancelled.iter() {
+        balances.refund_order(order, side, base_token, ctx.accounts.market.tick_size)?;
+    }
+
+    Ok(())
+}
+
+// cancels every order of the user on one side or both sides (side is None) of one book,
+// or of both books when the second orderbook is provided, and unlocks the funds in balances
+pub fn cancel_all_orders(ctx: Context<CancelAllOrders>, side: Option<Side>) -> Result<()> {
+    let uid = ctx.accounts.user_market_pda.uid;
     let balances = &mut ctx.accounts.balances.load_mut()?;
-    match side {
-        Side::Bid => {
-            let orders_side = &mut orderbook.asks;
-            let mut i = orders_side.best_order_idx;
-
-            while filled_amount < amount && i != 0 {
-                let order = orders_side.orders.get_mut(i as usize).unwrap();
-                if order.expire_at != 0 && order.expire_at < Clock::get()?.unix_timestamp as u64 {
-                    // would check if the order is expired, if yes, remove it
-                    balances.credit_account(order.uid, order.size * order.price, 0)?;
-                    i = order.next;
-                    orders_side.remove_order(i);
-                    continue;
-                }
-                let amount_to_fill = std::cmp::min(order.size, amount - filled_amount);
-                order.size -= amount_to_fill;
-                total_cost += amount_to_fill * order.price;
-                filled_amount += amount_to_fill;
-                balances.credit_account(order.uid, amount_to_fill * order.price, 0)?;
-                i = order.next;
-                if order.size == 0 {
-                    orders_side.remove_order(i);
-                }
-            }
+
+    let mut orderbooks = vec![&ctx.accounts.orderbook];
+    if let Some(other_orderbook) = ctx.accounts.other_orderbook.as_ref() {
+        if other_orderbook.key() == ctx.accounts.orderbook.key() {
+            return err!(EmberErr::SameOrderBook);
         }
-        Side::Ask => {
-            let base_mint = orderbook.base_mint;
-            let orders_side = &mut orderbook.bids;
-            let mut i = orders_side.best_order_idx;
-
-            while filled_amount < amount && i != 0 {
-                let order = orders_side.orders.get_mut(i as usize).unwrap();
-                let amount_to_fill = std::cmp::min(order.size, amount - filled_amount);
-                if order.expire_at != 0 && order.expire_at < Clock::get()?.unix_timestamp as u64 {
-                    // would check if the order is expired, if yes, remove it
-                    if base_mint == ctx.accounts.market.outcome_1_key {
-                        balances.credit_account(order.uid, order.size, 1)?;
-                    } else if base_mint == ctx.accounts.market.outcome_2_key {
-                        balances.credit_account(order.uid, order.size, 2)?;
-                    }
-                    i = order.next;
-                    orders_side.remove_order(i);
-                    continue;
-                }
-                order.size -= amount_to_fill;
-                total_cost += amount_to_fill * order.price;
-                filled_amount += amount_to_fill;
-                if base_mint == ctx.accounts.market.outcome_1_key {
-                    balances.credit_account(order.uid, amount_to_fill, 1)?;
-                } else if base_mint == ctx.accounts.market.outcome_2_key {
-                    balances.credit_account(order.uid, amount_to_fill, 2)?;
-                } else {
-                    return err!(EmberErr::InvalidMarket);
-                }
-                i = order.next;
-                if order.size == 0 {
-                    orders_side.remove_order(i);
-                }
+        orderbooks.push(other_orderbook);
+    }
+
+    let mut cancelled_count = 0;
+    for orderbook in orderbooks {
+        let base_token = ctx.accounts.market.base_token(orderbook.key())?;
+        let orderbook = &mut *orderbook.load_mut()?;
+        for (book_side, book) in [(Side::Bid, &mut orderbook.bids), (Side::Ask, &mut orderbook.asks)] {
+            if matches!(side, Some(side) if side != book_side) {
+                continue;
+            }
+            for order in book.remove_user_orders(uid, None).iter() {
+                balances.refund_order(order, book_side, base_token, ctx.accounts.market.tick_size)?;
+                cancelled_count += 1;
             }
         }
     }
 
+    msg!("cancelled {} orders", cancelled_count);
+    Ok(())
+}
+
+// takes from the book up to amount base lots, never at a price worse than worst_price ticks, and fails
+// if less than min_fill lots get filled. settles exactly the filled base against the quote it cost at the makers' prices,
+// the fill is returned to the caller so routers can chain orders
+pub fn place_market_order(
+    ctx: Context<PlaceMarketOrder>,
+    side: Side,
+    amount: u64,
+    worst_price: u64,
+    min_fill: u64,
+    self_trade_behavior: SelfTradeBehavior,
+) -> Result<MarketOrderResult> {
+    let taker = TakerOrder {
+        side,
+        limit_price: worst_price,
+        size: amount,
+        max_quote: u64::MAX,
+        uid: ctx.accounts.user_market_pda.uid,
+        self_trade_behavior,
+        taker_fee_bps: user_fees(&ctx.accounts.market, &ctx.accounts.fee_tiers, &ctx.accounts.user_account)?.1,
+    };
+    execute_market_order(ctx, taker, min_fill, None)
+}
+
+// market bid sized by the quote to spend ("put $50 on YES"), buys as much as quote_budget pays for
+// without going over it, the budget is in quote lots and covers the taker fee. the unspent quote stays with the user and is reported in the result
+pub fn place_market_bid_by_quote(
+    ctx: Context<PlaceMarketOrder>,
+    quote_budget: u64,
+    worst_price: u64,
+    min_fill: u64,
+    self_trade_behavior: SelfTradeBehavior,
+) -> Result<MarketOrderResult> {
+    let taker = TakerOrder {
+        side: Side::Bid,
+        limit_price: worst_price,
+        size: u64::MAX,
+        max_quote: quote_budget,
+        uid: ctx.accounts.user_market_pda.uid,
+        self_trade_behavior,
+        taker_fee_bps: user_fees(&ctx.accounts.market, &ctx.accounts.fee_tiers, &ctx.accounts.user_account)?.1,
+    };
+    execute_market_order(ctx, taker, min_fill, Some(quote_budget))
+}
+
+fn execute_market_order(
+    ctx: Context<PlaceMarketOrder>,
+    taker: TakerOrder,
+    min_fill: u64,
+    quote_budget: Option<u64>,
+) -> Result<MarketOrderResult> {
+    let market = &ctx.accounts.market;
+    market.require_open()?;
+    let base_token = market.base_token(ctx.accounts.orderbook.key())?;
+    let other_base_token = market.base_token(ctx.accounts.other_orderbook.key())?;
+    ctx.accounts.complete_set.validate(market)?;
+    let orderbook = &mut ctx.accounts.orderbook.load_mut()?;
+    let other_orderbook = &mut *ctx.accounts.other_orderbook.load_mut()?;
+    let event_queue = &mut ctx.accounts.event_queue.load_mut()?;
+
+    let complement = complement(
+        &ctx.accounts.complete_set,
+        market,
+        other_orderbook,
+        other_base_token,
+        taker.side,
+    );
+    let tick_size = market.tick_size;
+    let fill = orderbook.match_order(&taker, event_queue, base_token, tick_size, complement)?;
+    if fill.filled < min_fill {
+        return err!(EmberErr::MinimumFillNotReached);
+    }
+
     drop(orderbook);
-    drop(balances);
+    drop(event_queue);
 
-    let (payer, receiver, vault_to, vault_from) = match side {
+    let (payer, receiver, vault_to, vault_from, pay_amount, receive_amount) = match taker.side {
         Side::Ask => (
             &ctx.accounts.base_account,
             &ctx.accounts.quote_account,
             &ctx.accounts.base_vault,
             &ctx.accounts.quote_vault,
+            ctx.accounts.market.base_lots_to_native(fill.filled)?,
+            ctx.accounts.market.quote_lots_to_native(fill.quote_amount - fill.taker_fee)?,
         ),
         Side::Bid => (
             &ctx.accounts.quote_account,
             &ctx.accounts.base_account,
             &ctx.accounts.quote_vault,
             &ctx.accounts.base_vault,
+            ctx.accounts.market.quote_lots_to_native(fill.quote_amount + fill.taker_fee)?,
+            ctx.accounts.market.base_lots_to_native(fill.filled)?,
         ),
     };
 
     let token_program_info = &ctx.accounts.token_program.to_account_info();
     let signer = ctx.accounts.signer.to_account_info();
-    // transfer the trade amount from the user to the vault
+    // transfer what the fills cost from the user to the vault
     transfer_tokens(
         signer,
         payer.to_account_info(),
         vault_to.to_account_info(),
         token_program_info.clone(),
-        amount,
+        pay_amount,
     )?;
 
-    // transfer the trade amount from the vault to the user
     let bump = ctx.bumps.get("market_auth_pda").unwrap();
-    let market = ctx.accounts.market.key();
-    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market.as_ref(), &[*bump]]];
-
+    let market_key = market.key();
+    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market_key.as_ref(), &[*bump]]];
     let vault_auth = &ctx.accounts.market_auth_pda;
 
+    // fills against the other outcome book are backed by sets minted or merged between the vaults
+    ctx.accounts.complete_set.settle_complement_fills(
+        taker.side,
+        vault_auth.to_account_info(),
+        ctx.accounts.quote_vault.to_account_info(),
+        token_program_info.clone(),
+        market.base_lots_to_native(fill.complement_filled)?,
+        seeds,
+    )?;
+
+    let volume = market.quote_lots_to_native(fill.quote_amount)?;
+    let fees = market.quote_lots_to_native(fill.fees)? - credit_referrer(market, &mut ctx.accounts.referral, &fill, volume)?;
+    if fees > 0 {
+        transfer_tokens_signed(
+            vault_auth.to_account_info(),
+            ctx.accounts.quote_vault.to_account_info(),
+            ctx.accounts.fee_vault.to_account_info(),
+            token_program_info.clone(),
+            fees,
+            seeds,
+        )?;
+    }
+
+    // transfer what was bought from the vault to the user
+
     transfer_tokens_signed(
         vault_auth.to_account_info(),
         vault_from.to_account_info(),
         receiver.to_account_info(),
         token_program_info.clone(),
-        amount,
+        receive_amount,
         seeds,
     )?;
 
-    Ok(())
+    ctx.accounts.user_account.add_volume(volume, Clock::get()?.unix_timestamp as u64);
+    let user_market_pda = &mut ctx.accounts.user_market_pda;
+    user_market_pda.volume = user_market_pda.volume.saturating_add(volume);
+
+    let result = MarketOrderResult::new(&fill, quote_budget, tick_size);
+    msg!("market order filled {} at an average price of {}", result.filled, result.average_price);
+    Ok(result)
 }
 
 pub fn clear_expired_orders(ctx: Context<ClearExpiredOrders>) -> Result<()> {
     // this instruction is a convenient ix to loop over the orderbook and remove expired orders.
     // it will work in a crank style, but won't be necessary since expired orders won't be executing anyway
-    let orderbook = &mut ctx.accounts.orderbook.load_mut()?;
+    let base_token = ctx.accounts.market.base_token(ctx.accounts.orderbook.key())?;
+    let orderbook = &mut *ctx.accounts.orderbook.load_mut()?;
     let balances = &mut ctx.accounts.balances.load_mut()?;
-    let mut orders_to_remove = Vec::new();
-    for order in orderbook.bids.orders.iter() {
-        if order.expire_at != 0 && order.expire_at < Clock::get()?.unix_timestamp as u64 {
-            balances.credit_account(order.uid, order.size * order.price, 0)?;
-            orders_to_remove.push(order.uid);
+    let now = Clock::get()?.unix_timestamp as u64;
+
+    for (side, book) in [(Side::Bid, &mut orderbook.bids), (Side::Ask, &mut orderbook.asks)] {
+        let expired: Vec<u64> = book
+            .orders()
+            .filter(|(_, order)| order.is_expired(now))
+            .map(|(order_id, _)| order_id)
+            .collect();
+        for order_id in expired {
+            if let Some(order) = book.remove_order(order_id) {
+                balances.refund_order(&order, side, base_token, ctx.accounts.market.tick_size)?;
+            }
         }
     }
-    for order in orders_to_remove {
-        orderbook.bids.remove_order(order);
-    }
 
-    let mut orders_to_remove = Vec::new();
-    for order in orderbook.asks.orders.iter_mut() {
-        if order.expire_at != 0 && order.expire_at < Clock::get()?.unix_timestamp as u64 {
-            balances.credit_account(order.uid, order.size, 0)?;
-            orders_to_remove.push(order.uid);
+    Ok(())
+}
+
+// permissionless crank, settles up to limit events from the queue, oldest first, on the makers' balances.
+// the remaining accounts are the user account and the market user account of the maker of each fill
+// event, in order, to add the fill to the maker's volume. they're optional, fills past the accounts given
+// are settled without counting the maker's volume so a missing account never holds the queue up
+pub fn consume_events<'info>(ctx: Context<'_, '_, '_, 'info, ConsumeEvents<'info>>, limit: u64) -> Result<()> {
+    let event_queue = &mut ctx.accounts.event_queue.load_mut()?;
+    let balances = &mut ctx.accounts.balances.load_mut()?;
+    let market = &ctx.accounts.market;
+    let now = Clock::get()?.unix_timestamp as u64;
+    let mut maker_accounts = ctx.remaining_accounts.chunks_exact(2);
+
+    let mut consumed = 0;
+    while consumed < limit {
+        let event = match event_queue.peek() {
+            Some(event) => *event,
+            None => break,
+        };
+        if event.event_type == EVENT_FILL {
+            if let Some(accounts) = maker_accounts.next() {
+                let volume = market.quote_lots_to_native(quote_lots(event.size, event.price, market.tick_size)?)?;
+                add_maker_volume(accounts, &market.key(), event.maker_uid, volume, now)?;
+            }
         }
-    }
-    for order in orders_to_remove {
-        orderbook.asks.remove_order(order);
+        balances.settle_event(&event, market.tick_size)?;
+        event_queue.pop();
+        consumed += 1;
     }
 
+    msg!("consumed {} events, {} left in the queue", consumed, event_queue.count);
     Ok(())
 }
 
+fn add_maker_volume(accounts: &[AccountInfo], market: &Pubkey, uid: u64, volume: u64, now: u64) -> Result<()> {
+    let mut user_account: Account<User> = Account::try_from(&accounts[0])?;
+    let mut user_market_pda: Account<MarketSpecificUser> = Account::try_from(&accounts[1])?;
+    let (expected, _) =
+        Pubkey::find_program_address(&[user_account.owner.as_ref(), market.as_ref()], &crate::ID);
+    if user_market_pda.key() != expected || user_market_pda.uid != uid {
+        return err!(EmberErr::InvalidMakerAccounts);
+    }
+
+    user_account.add_volume(volume, now);
+    user_market_pda.volume = user_market_pda.volume.saturating_add(volume);
+    user_account.exit(&crate::ID)?;
+    user_market_pda.exit(&crate::ID)
+}
+
 #[derive(Accounts)]
 pub struct CancelLimitOrder<'info> {
     #[account(mut)]
@@ -263,6 +588,25 @@ pub struct CancelLimitOrder<'info> {
     #[account(mut, seeds = [signer.key().as_ref(), market.key().as_ref()], bump)]
     pub user_market_pda: Account<'info, MarketSpecificUser>,
 
+    #[account(mut, constraint = balances.key() == market.balances)]
+    pub balances: AccountLoader<'info, UsersBalances>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAllOrders<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub market: Account<'info, Market>,
+    #[account(mut, constraint = orderbook.key() == market.orderbook_state_1 || orderbook.key() == market.orderbook_state_2)]
+    pub orderbook: AccountLoader<'info, OrderBookState>,
+    // optional, the market's other orderbook to cancel on both books at once
+    #[account(mut, constraint = other_orderbook.key() == market.orderbook_state_1 || other_orderbook.key() == market.orderbook_state_2)]
+    pub other_orderbook: Option<AccountLoader<'info, OrderBookState>>,
+
+    #[account(mut, seeds = [signer.key().as_ref(), market.key().as_ref()], bump)]
+    pub user_market_pda: Account<'info, MarketSpecificUser>,
+
+    #[account(mut, constraint = balances.key() == market.balances)]
     pub balances: AccountLoader<'info, UsersBalances>,
 }
 
@@ -275,10 +619,31 @@ pub struct PlaceLimitOrder<'info> {
     #[account(mut, constraint = orderbook.key() == market.orderbook_state_1 || orderbook.key() == market.orderbook_state_2)]
     pub orderbook: AccountLoader<'info, OrderBookState>,
 
+    #[account(mut, seeds = [signer.key().as_ref(), USER_ACCOUNT_PDA_SEED], bump)]
+    pub user_account: Box<Account<'info, User>>,
     #[account(mut, seeds = [signer.key().as_ref(), market.key().as_ref()], bump)]
     pub user_market_pda: Account<'info, MarketSpecificUser>,
+    #[account(seeds = [FEE_TIERS_SEED], bump)]
+    pub fee_tiers: Option<Account<'info, FeeTiers>>,
+    // referral account of the user's referrer on this market, if it has one
+    #[account(mut, seeds = [REFERRAL_SEED, user_account.referrer.as_ref(), market.key().as_ref()], bump)]
+    pub referral: Option<Account<'info, ReferralFees>>,
     #[account(mut, constraint = balances.key() == market.balances)]
     pub balances: AccountLoader<'info, UsersBalances>,
+    #[account(mut, constraint = event_queue.key() == market.event_queue)]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+
+    // the market's other orderbook and the accounts to match orders across the two books
+    #[account(mut, constraint = other_orderbook.key() != orderbook.key() && (other_orderbook.key() == market.orderbook_state_1 || other_orderbook.key() == market.orderbook_state_2))]
+    pub other_orderbook: AccountLoader<'info, OrderBookState>,
+    #[account(mut, seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    #[account(mut, constraint = quote_vault.key() == market.quote_vault @ EmberErr::InvalidMarket)]
+    pub quote_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = fee_vault.key() == market.fee_vault @ EmberErr::InvalidMarket)]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+    pub complete_set: CompleteSet<'info>,
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
@@ -288,18 +653,35 @@ pub struct PlaceMarketOrder<'info> {
     pub market: Box<Account<'info, Market>>,
     #[account(mut, seeds = [signer.key().as_ref(), USER_ACCOUNT_PDA_SEED], bump)]
     pub user_account: Box<Account<'info, User>>,
+    #[account(mut, seeds = [signer.key().as_ref(), market.key().as_ref()], bump)]
+    pub user_market_pda: Box<Account<'info, MarketSpecificUser>>,
+    #[account(seeds = [FEE_TIERS_SEED], bump)]
+    pub fee_tiers: Option<Account<'info, FeeTiers>>,
+    // referral account of the user's referrer on this market, if it has one
+    #[account(mut, seeds = [REFERRAL_SEED, user_account.referrer.as_ref(), market.key().as_ref()], bump)]
+    pub referral: Option<Account<'info, ReferralFees>>,
     #[account(mut, constraint = orderbook.key() == market.orderbook_state_1 || orderbook.key() == market.orderbook_state_2)]
     pub orderbook: AccountLoader<'info, OrderBookState>,
-    #[account(mut, constraint = balances.key() == market.balances)]
-    pub balances: AccountLoader<'info, UsersBalances>,
+    #[account(mut, constraint = event_queue.key() == market.event_queue)]
+    pub event_queue: AccountLoader<'info, EventQueue>,
     #[account(mut, seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
     pub market_auth_pda: Account<'info, Auth>,
 
-    // TODO: add relevant checks
-    pub base_account: Account<'info, TokenAccount>,
-    pub quote_account: Account<'info, TokenAccount>,
-    pub base_vault: Account<'info, TokenAccount>,
-    pub quote_vault: Account<'info, TokenAccount>,
+    #[account(mut, constraint = base_account.mint == base_vault.mint @ EmberErr::InvalidToken)]
+    pub base_account: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = quote_account.mint == market.quote_key @ EmberErr::InvalidToken)]
+    pub quote_account: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = market.confirm_base_account(orderbook.key(), base_vault.key()) @ EmberErr::InvalidMarket)]
+    pub base_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = quote_vault.key() == market.quote_vault @ EmberErr::InvalidMarket)]
+    pub quote_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = fee_vault.key() == market.fee_vault @ EmberErr::InvalidMarket)]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+
+    // the market's other orderbook and the accounts to match orders across the two books
+    #[account(mut, constraint = other_orderbook.key() != orderbook.key() && (other_orderbook.key() == market.orderbook_state_1 || other_orderbook.key() == market.orderbook_state_2))]
+    pub other_orderbook: AccountLoader<'info, OrderBookState>,
+    pub complete_set: CompleteSet<'info>,
 
     pub token_program: Program<'info, Token>,
 }
@@ -312,3 +694,12 @@ pub struct ClearExpiredOrders<'info> {
     #[account(mut, constraint = balances.key() == market.balances)]
     pub balances: AccountLoader<'info, UsersBalances>,
 }
+
+#[derive(Accounts)]
+pub struct ConsumeEvents<'info> {
+    pub market: Account<'info, Market>,
+    #[account(mut, constraint = event_queue.key() == market.event_queue)]
+    pub event_queue: AccountLoader<'info, EventQueue>,
+    #[account(mut, constraint = balances.key() == market.balances)]
+    pub balances: AccountLoader<'info, UsersBalances>,
+}
@@ -0,0 +1,195 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use crate::consts::{
+    MARKET_AUTH_SEED, MAX_ORDER_PRICE, MAX_REWARDS_SAMPLE_INTERVAL, MIN_REWARDS_MULTIPLIER, PROGRAM_CONFIG_SEED,
+    REWARDS_PRECISION, ROLE_FEE_ADMIN,
+};
+use crate::ember_errors::EmberErr;
+use crate::instructions::confirm_admin;
+use crate::state::orderbook::{Order, OrderBookState};
+use crate::state::state::{Auth, Market, MarketSpecificUser, ProgramConfig, UsersBalances};
+use crate::utils::transfer_tokens_signed;
+
+// creates the reward vault of the market, it's funded by transferring reward tokens to it
+pub fn initialize_rewards(ctx: Context<InitializeRewards>, rewards_multiplier: u64, max_spread: u64) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_FEE_ADMIN)?;
+
+    let market = &mut ctx.accounts.market;
+    market.reward_vault = ctx.accounts.reward_vault.key();
+    market.last_rewards_sample = Clock::get()?.unix_timestamp as u64;
+    set_rewards_params(market, rewards_multiplier, max_spread)
+}
+
+// new parameters count from the last sample on, a max_spread of 0 stops the rewards
+pub fn set_rewards(ctx: Context<SetRewards>, rewards_multiplier: u64, max_spread: u64) -> Result<()> {
+    confirm_admin(&ctx.accounts.config, &ctx.accounts.signer, ROLE_FEE_ADMIN)?;
+    require!(ctx.accounts.market.rewards_multiplier != 0, EmberErr::InvalidMarket);
+
+    set_rewards_params(&mut ctx.accounts.market, rewards_multiplier, max_spread)
+}
+
+fn set_rewards_params(market: &mut Market, rewards_multiplier: u64, max_spread: u64) -> Result<()> {
+    if rewards_multiplier < MIN_REWARDS_MULTIPLIER {
+        return err!(EmberErr::RewardsMultiplierTooSmall);
+    }
+    if max_spread > MAX_ORDER_PRICE {
+        return err!(EmberErr::InvalidPrice);
+    }
+    market.rewards_multiplier = rewards_multiplier;
+    market.rewards_max_spread = max_spread;
+
+    msg!("liquidity rewards set, multiplier {}, max spread {}", rewards_multiplier, max_spread);
+    Ok(())
+}
+
+// permissionless crank, pays the orders resting on both books for the time since the last sample.
+// a book without a bid or an ask has no midpoint and earns nothing. each side of a book is walked from
+// its best order outward and only up to the first order past the max spread, so the crank's cost
+// follows the orders near the midpoint rather than the size of the book
+pub fn sample_rewards(ctx: Context<SampleRewards>) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+    let now = Clock::get()?.unix_timestamp as u64;
+    let since = std::cmp::max(market.last_rewards_sample, now.saturating_sub(MAX_REWARDS_SAMPLE_INTERVAL));
+    market.last_rewards_sample = now;
+    if market.rewards_multiplier == 0 || market.rewards_max_spread == 0 || since >= now || !market.is_open(now) {
+        return Ok(());
+    }
+
+    let orderbook_1 = &ctx.accounts.orderbook_state_1.load()?;
+    let orderbook_2 = &ctx.accounts.orderbook_state_2.load()?;
+    let balances = &mut ctx.accounts.balances.load_mut()?;
+
+    let mut total: u64 = 0;
+    for (book, other) in [(orderbook_1, orderbook_2), (orderbook_2, orderbook_1)] {
+        let (bid, ask) = match book.best_prices(other, market.max_price) {
+            Some(prices) => prices,
+            None => continue,
+        };
+        for orders in [book.bids.orders_by_priority(), book.asks.orders_by_priority()] {
+            for (_, order) in orders {
+                let reward = match quote_reward(order, bid + ask, market, since, now)? {
+                    Some(reward) => reward,
+                    // the rest of the side is further from the midpoint
+                    None => break,
+                };
+                if reward == 0 {
+                    continue;
+                }
+                let balance = &mut balances.users[order.uid as usize];
+                balance.rewards = balance.rewards.checked_add(reward).ok_or(EmberErr::AmountOverflow)?;
+                total = total.checked_add(reward).ok_or(EmberErr::AmountOverflow)?;
+            }
+        }
+    }
+
+    msg!("sampled {} in liquidity rewards", total);
+    Ok(())
+}
+
+// reward of a resting order for its time on the book between since and now. it scores its size times the
+// square of how close it quotes to the midpoint, none for an order max spread ticks away or more. distances
+// are in half ticks so the midpoint of the best bid and ask, mid_2 being their sum, is never rounded
+fn quote_reward(order: &Order, mid_2: u64, market: &Market, since: u64, now: u64) -> Result<Option<u64>> {
+    let spread = 2 * market.rewards_max_spread as u128;
+    let distance = (2 * order.price as u128).abs_diff(mid_2 as u128);
+    if distance >= spread {
+        return Ok(None);
+    }
+    if order.is_expired(now) {
+        return Ok(Some(0));
+    }
+    let closeness = spread - distance;
+    let elapsed = now - std::cmp::max(since, order.placed_at);
+
+    let reward = (order.size as u128)
+        .checked_mul(elapsed as u128)
+        .and_then(|reward| reward.checked_mul(market.rewards_multiplier as u128))
+        .and_then(|reward| reward.checked_mul(closeness * closeness))
+        .ok_or(EmberErr::AmountOverflow)?
+        / (spread * spread * REWARDS_PRECISION as u128);
+    u64::try_from(reward).map(Some).map_err(|_| error!(EmberErr::AmountOverflow))
+}
+
+// pays out the rewards the user earned, as much as the reward vault holds, the rest stays owed
+pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+    let balances = &mut ctx.accounts.balances.load_mut()?;
+    let balance = &mut balances.users[ctx.accounts.user_market_pda.uid as usize];
+    let amount = std::cmp::min(balance.rewards, ctx.accounts.reward_vault.amount);
+    balance.rewards -= amount;
+
+    let bump = ctx.bumps.get("market_auth_pda").unwrap();
+    let market = ctx.accounts.market.key();
+    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market.as_ref(), &[*bump]]];
+    transfer_tokens_signed(
+        ctx.accounts.market_auth_pda.to_account_info(),
+        ctx.accounts.reward_vault.to_account_info(),
+        ctx.accounts.receiver.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        amount,
+        seeds,
+    )?;
+
+    msg!("claimed {} in liquidity rewards, {} still owed", amount, balance.rewards);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeRewards<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut, constraint = market.rewards_multiplier == 0 @ EmberErr::InvalidMarket)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    pub reward_token: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        token::mint = reward_token,
+        token::authority = market_auth_pda,
+        payer = signer)]
+    pub reward_vault: Box<Account<'info, TokenAccount>>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetRewards<'info> {
+    pub signer: Signer<'info>,
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+}
+
+#[derive(Accounts)]
+pub struct SampleRewards<'info> {
+    #[account(mut)]
+    pub market: Box<Account<'info, Market>>,
+    #[account(constraint = orderbook_state_1.key() == market.orderbook_state_1)]
+    pub orderbook_state_1: AccountLoader<'info, OrderBookState>,
+    #[account(constraint = orderbook_state_2.key() == market.orderbook_state_2)]
+    pub orderbook_state_2: AccountLoader<'info, OrderBookState>,
+    #[account(mut, constraint = balances.key() == market.balances)]
+    pub balances: AccountLoader<'info, UsersBalances>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    pub signer: Signer<'info>,
+    pub market: Box<Account<'info, Market>>,
+    #[account(seeds = [signer.key().as_ref(), market.key().as_ref()], bump)]
+    pub user_market_pda: Account<'info, MarketSpecificUser>,
+    #[account(mut, constraint = balances.key() == market.balances)]
+    pub balances: AccountLoader<'info, UsersBalances>,
+    #[account(seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    #[account(mut, constraint = reward_vault.key() == market.reward_vault @ EmberErr::InvalidMarket)]
+    pub reward_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = receiver.mint == reward_vault.mint @ EmberErr::InvalidToken)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
@@ -2,9 +2,10 @@ use anchor_lang::prelude::*;
 use anchor_spl::token;
 use anchor_spl::token::{Token, TokenAccount};
 
-use crate::consts::{MARKET_AUTH_SEED, USER_ACCOUNT_PDA_SEED};
-use crate::state::state::{Auth, Balance, Market, MarketSpecificUser, User, UsersBalances};
-use crate::utils::transfer_tokens;
+use crate::consts::{MARKET_AUTH_SEED, REFERRAL_SEED, USER_ACCOUNT_PDA_SEED};
+use crate::ember_errors::EmberErr;
+use crate::state::state::{Auth, Balance, Market, MarketSpecificUser, ReferralFees, User, UsersBalances};
+use crate::utils::{transfer_tokens, transfer_tokens_signed};
 
 pub fn create_user_account(ctx: Context<CreateUserAccount>) -> Result<()> {
     ctx.accounts.user_account.volume = 0;
@@ -13,6 +14,45 @@ pub fn create_user_account(ctx: Context<CreateUserAccount>) -> Result<()> {
     ctx.accounts.user_account.has_open_orders = false;
     ctx.accounts.user_account.losing_bets = 0;
     ctx.accounts.user_account.winning_bets = 0;
+    ctx.accounts.user_account.period = 0;
+    ctx.accounts.user_account.period_volume = 0;
+    ctx.accounts.user_account.previous_period_volume = 0;
+    ctx.accounts.user_account.referrals = 0;
+    ctx.accounts.user_account.referrer = Pubkey::default();
+    if let Some(referrer) = &mut ctx.accounts.referrer {
+        referrer.referrals += 1;
+        ctx.accounts.user_account.referrer = referrer.key();
+        msg!("referred by {}", referrer.owner);
+    }
+    Ok(())
+}
+
+// a referrer needs one on every market it earns referral fees on, until then its share goes to the fee vault.
+// anyone can create it for a referrer, a referred user included
+pub fn create_referral_account(ctx: Context<CreateReferralAccount>) -> Result<()> {
+    ctx.accounts.referral.fees = 0;
+    ctx.accounts.referral.total_fees = 0;
+    ctx.accounts.referral.referred_volume = 0;
+    Ok(())
+}
+
+pub fn claim_referral_fees(ctx: Context<ClaimReferralFees>) -> Result<()> {
+    let amount = ctx.accounts.referral.fees;
+    ctx.accounts.referral.fees = 0;
+
+    let bump = ctx.bumps.get("market_auth_pda").unwrap();
+    let market = ctx.accounts.market.key();
+    let seeds: &[&[&[u8]]] = &[&[MARKET_AUTH_SEED, market.as_ref(), &[*bump]]];
+    transfer_tokens_signed(
+        ctx.accounts.market_auth_pda.to_account_info(),
+        ctx.accounts.quote_vault.to_account_info(),
+        ctx.accounts.receiver.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        amount,
+        seeds,
+    )?;
+
+    msg!("claimed {} in referral fees", amount);
     Ok(())
 }
 
@@ -24,6 +64,7 @@ pub fn create_market_account(ctx: Context<CreateMarketAccount>) -> Result<()> {
         base_1: 0,
         base_2: 0,
         quote: 0,
+        rewards: 0,
     };
     balances.users[user_uid as usize] = user_balance;
     msg!("created user with UID {}", user_uid);
@@ -34,12 +75,17 @@ pub fn create_market_account(ctx: Context<CreateMarketAccount>) -> Result<()> {
 }
 
 pub fn claim_balance(ctx: Context<ClaimBalance>) -> Result<()> {
+    // the winning vault is already redeemed while the users' balances are still being converted
+    if ctx.accounts.market.settled_users > 0 && !ctx.accounts.market.settled {
+        return err!(EmberErr::SettlementInProgress);
+    }
     let balances = &mut ctx.accounts.balances.load_mut()?;
     let user_balance = &mut balances.users[ctx.accounts.user_market_pda.uid as usize];
-    let base_balance_1 = user_balance.base_1;
-    let base_balance_2 = user_balance.base_2;
-    // 100 is the incerement of the markets in here, so in case of the usdc market, 1 means 100 lots, or 1 cent/0.01 usdc
-    let quote_balance = user_balance.quote * 100;
+    // balances are kept in lots, the transfers are in native tokens
+    let market = &ctx.accounts.market;
+    let base_balance_1 = market.base_lots_to_native(user_balance.base_1)?;
+    let base_balance_2 = market.base_lots_to_native(user_balance.base_2)?;
+    let quote_balance = market.quote_lots_to_native(user_balance.quote)?;
     user_balance.base_1 = 0;
     user_balance.base_2 = 0;
     user_balance.quote = 0;
@@ -90,20 +136,30 @@ pub fn claim_balance(ctx: Context<ClaimBalance>) -> Result<()> {
     Ok(())
 }
 
+// amounts are in lots, the same units as the balances and the orders
 pub fn deposit_balance(
     ctx: Context<DepositBalance>,
     quote_amount: u64,
     base_1_amount: u64,
     base_2_amount: u64,
 ) -> Result<()> {
+    // outcome tokens deposited once settlement started would never be converted
+    if ctx.accounts.market.settled || ctx.accounts.market.settled_users > 0 {
+        return err!(EmberErr::MarketSettled);
+    }
     let balances = &mut ctx.accounts.balances.load_mut()?;
     let user_balance = &mut balances.users[ctx.accounts.user_market_pda.uid as usize];
     user_balance.quote += quote_amount;
     user_balance.base_1 += base_1_amount;
     user_balance.base_2 += base_2_amount;
 
+    let market = &ctx.accounts.market;
+    let quote_amount = market.quote_lots_to_native(quote_amount)?;
+    let base_1_amount = market.base_lots_to_native(base_1_amount)?;
+    let base_2_amount = market.base_lots_to_native(base_2_amount)?;
+
     let auth = ctx.accounts.signer.to_account_info();
-    let token_account_info = ctx.accounts.quote_account.to_account_info();
+    let token_account_info = ctx.accounts.token_program.to_account_info();
 
     // topping up quote balance
     let quote_account = ctx.accounts.quote_account.to_account_info();
@@ -146,20 +202,58 @@ pub struct CreateUserAccount<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
     pub market: Account<'info, Market>,
-    #[account(init, seeds = [signer.key().as_ref(), USER_ACCOUNT_PDA_SEED], bump, payer = signer, space = std::mem::size_of::< User > ())]
+    #[account(init, seeds = [signer.key().as_ref(), USER_ACCOUNT_PDA_SEED], bump, payer = signer, space = 8 + std::mem::size_of::< User > ())]
     pub user_account: Account<'info, User>,
+    #[account(mut, constraint = referrer.owner != signer.key() @ EmberErr::SelfReferral)]
+    pub referrer: Option<Account<'info, User>>,
     pub rent: Sysvar<'info, Rent>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct CreateReferralAccount<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub market: Box<Account<'info, Market>>,
+    // the referrer's user account
+    #[account(seeds = [user_account.owner.as_ref(), USER_ACCOUNT_PDA_SEED], bump)]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init,
+        seeds = [REFERRAL_SEED, user_account.key().as_ref(), market.key().as_ref()],
+        bump,
+        payer = signer,
+        space = 8 + std::mem::size_of::<ReferralFees>())]
+    pub referral: Account<'info, ReferralFees>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralFees<'info> {
+    pub signer: Signer<'info>,
+    pub market: Box<Account<'info, Market>>,
+    #[account(seeds = [signer.key().as_ref(), USER_ACCOUNT_PDA_SEED], bump)]
+    pub user_account: Account<'info, User>,
+    #[account(mut, seeds = [REFERRAL_SEED, user_account.key().as_ref(), market.key().as_ref()], bump)]
+    pub referral: Account<'info, ReferralFees>,
+    #[account(seeds = [MARKET_AUTH_SEED, market.key().as_ref()], bump)]
+    pub market_auth_pda: Account<'info, Auth>,
+    #[account(mut, constraint = quote_vault.key() == market.quote_vault @ EmberErr::InvalidMarket)]
+    pub quote_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = receiver.mint == market.quote_key @ EmberErr::InvalidToken)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 pub struct CreateMarketAccount<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
     pub market: Box<Account<'info, Market>>,
     pub user_account: Account<'info, User>,
-    #[account(init, seeds = [signer.key().as_ref(), market.key().as_ref()], bump, payer = signer, space = std::mem::size_of::< MarketSpecificUser > ())]
+    #[account(init, seeds = [signer.key().as_ref(), market.key().as_ref()], bump, payer = signer, space = 8 + std::mem::size_of::< MarketSpecificUser > ())]
     pub user_market_pda: Account<'info, MarketSpecificUser>,
+    #[account(mut, constraint = balances.key() == market.balances)]
     pub balances: AccountLoader<'info, UsersBalances>,
     pub rent: Sysvar<'info, Rent>,
     pub system_program: Program<'info, System>,
@@ -180,15 +274,15 @@ pub struct ClaimBalance<'info> {
     pub market_auth_pda: Account<'info, Auth>,
     #[account(mut, constraint = base_vault_1.key() == market.base_vault_1)]
     pub base_vault_1: Account<'info, TokenAccount>,
-    #[account(mut, constraint = base_vault_2.key() == market.base_vault_1)]
+    #[account(mut, constraint = base_vault_2.key() == market.base_vault_2)]
     pub base_vault_2: Account<'info, TokenAccount>,
     #[account(mut, constraint = quote_vault.key() == market.quote_vault)]
     pub quote_vault: Account<'info, TokenAccount>,
     #[account(mut, constraint = market.quote_key == quote_account.mint.key())]
     pub quote_account: Account<'info, TokenAccount>,
-    #[account(mut, constraint = market.outcome_1_key == quote_account.mint.key())]
+    #[account(mut, constraint = market.outcome_1_key == base_account_1.mint.key())]
     pub base_account_1: Account<'info, TokenAccount>,
-    #[account(mut, constraint = market.outcome_2_key == quote_account.mint.key())]
+    #[account(mut, constraint = market.outcome_2_key == base_account_2.mint.key())]
     pub base_account_2: Account<'info, TokenAccount>,
 
     pub token_program: Program<'info, Token>,
@@ -206,15 +300,15 @@ pub struct DepositBalance<'info> {
 
     #[account(mut, constraint = base_vault_1.key() == market.base_vault_1)]
     pub base_vault_1: Box<Account<'info, TokenAccount>>,
-    #[account(mut, constraint = base_vault_2.key() == market.base_vault_1)]
+    #[account(mut, constraint = base_vault_2.key() == market.base_vault_2)]
     pub base_vault_2: Box<Account<'info, TokenAccount>>,
     #[account(mut, constraint = quote_vault.key() == market.quote_vault)]
     pub quote_vault: Box<Account<'info, TokenAccount>>,
     #[account(mut, constraint = market.quote_key == quote_account.mint.key())]
     pub quote_account: Account<'info, TokenAccount>,
-    #[account(mut, constraint = market.outcome_1_key == quote_account.mint.key())]
+    #[account(mut, constraint = market.outcome_1_key == base_account_1.mint.key())]
     pub base_account_1: Box<Account<'info, TokenAccount>>,
-    #[account(mut, constraint = market.outcome_2_key == quote_account.mint.key())]
+    #[account(mut, constraint = market.outcome_2_key == base_account_2.mint.key())]
     pub base_account_2: Box<Account<'info, TokenAccount>>,
 
     pub token_program: Program<'info, Token>,
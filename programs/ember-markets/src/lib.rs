@@ -1,7 +1,10 @@
 use anchor_lang::prelude::*;
 
 use crate::instructions::*;
+use crate::state::order_type::{OrderType, SelfTradeBehavior};
+use crate::state::orderbook::MarketOrderResult;
 use crate::state::side::Side;
+use crate::state::state::FeeTier;
 
 mod consts;
 mod ember_errors;
@@ -15,30 +18,125 @@ declare_id!("9ERQkbvkLhxTUfw4vpRpcAtxvsUorn7HEXrGSxY5F6Zy");
 pub mod ember_markets {
     use super::*;
 
-    pub fn initialize_market(ctx: Context<InitializeMarket>) -> Result<()> {
-        instructions::initialize_market(ctx)
+    pub fn initialize_program_config(ctx: Context<InitializeProgramConfig>) -> Result<()> {
+        instructions::initialize_program_config(ctx)
+    }
+
+    pub fn set_program_config(ctx: Context<UpdateProgramConfig>, params: ProgramConfigParams) -> Result<()> {
+        instructions::set_program_config(ctx, params)
+    }
+
+    pub fn add_admin(ctx: Context<UpdateProgramConfig>, admin: Pubkey, roles: u64) -> Result<()> {
+        instructions::add_admin(ctx, admin, roles)
+    }
+
+    pub fn remove_admin(ctx: Context<UpdateProgramConfig>, admin: Pubkey) -> Result<()> {
+        instructions::remove_admin(ctx, admin)
+    }
+
+    pub fn initialize_market(
+        ctx: Context<InitializeMarket>,
+        tick_size: u64,
+        base_lot_size: u64,
+        quote_lot_size: u64,
+        end_time: u64,
+    ) -> Result<()> {
+        instructions::initialize_market(ctx, tick_size, base_lot_size, quote_lot_size, end_time)
+    }
+
+    pub fn create_market_with_condition(
+        ctx: Context<CreateMarketWithCondition>,
+        params: CreateMarketParams,
+    ) -> Result<()> {
+        instructions::create_market_with_condition(ctx, params)
+    }
+
+    pub fn refund_creator_bond(ctx: Context<RefundCreatorBond>) -> Result<()> {
+        instructions::refund_creator_bond(ctx)
+    }
+
+    pub fn slash_creator_bond(ctx: Context<SlashCreatorBond>) -> Result<()> {
+        instructions::slash_creator_bond(ctx)
+    }
+
+    pub fn claim_creator_fees(ctx: Context<ClaimCreatorFees>) -> Result<()> {
+        instructions::claim_creator_fees(ctx)
     }
 
     pub fn initialize_vaults(ctx: Context<InitializeVaults>) -> Result<()> {
         instructions::initialize_vaults(ctx)
     }
 
+    pub fn set_market_fees(ctx: Context<SetMarketFees>, maker_fee_bps: i64, taker_fee_bps: u64) -> Result<()> {
+        instructions::set_market_fees(ctx, maker_fee_bps, taker_fee_bps)
+    }
+
+    pub fn set_referral_share(ctx: Context<SetMarketFees>, referral_share_bps: u64) -> Result<()> {
+        instructions::set_referral_share(ctx, referral_share_bps)
+    }
+
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>) -> Result<()> {
+        instructions::withdraw_fees(ctx)
+    }
+
+    pub fn initialize_rewards(
+        ctx: Context<InitializeRewards>,
+        rewards_multiplier: u64,
+        max_spread: u64,
+    ) -> Result<()> {
+        instructions::initialize_rewards(ctx, rewards_multiplier, max_spread)
+    }
+
+    pub fn set_rewards(ctx: Context<SetRewards>, rewards_multiplier: u64, max_spread: u64) -> Result<()> {
+        instructions::set_rewards(ctx, rewards_multiplier, max_spread)
+    }
+
+    pub fn initialize_fee_tiers(ctx: Context<InitializeFeeTiers>) -> Result<()> {
+        instructions::initialize_fee_tiers(ctx)
+    }
+
+    pub fn set_fee_tiers(ctx: Context<SetFeeTiers>, tiers: Vec<FeeTier>) -> Result<()> {
+        instructions::set_fee_tiers(ctx, tiers)
+    }
+
     pub fn create_user_account(ctx: Context<CreateUserAccount>) -> Result<()> {
         instructions::create_user_account(ctx)
     }
 
+    pub fn create_referral_account(ctx: Context<CreateReferralAccount>) -> Result<()> {
+        instructions::create_referral_account(ctx)
+    }
+
+    pub fn claim_referral_fees(ctx: Context<ClaimReferralFees>) -> Result<()> {
+        instructions::claim_referral_fees(ctx)
+    }
+
     pub fn create_market_account(ctx: Context<CreateMarketAccount>) -> Result<()> {
         instructions::create_market_account(ctx)
     }
 
-    pub fn place_limit_order(
+    pub fn place_limit_order(ctx: Context<PlaceLimitOrder>, params: LimitOrderParams) -> Result<()> {
+        instructions::place_limit_order(ctx, params)
+    }
+
+    pub fn place_synthetic_ask(
         ctx: Context<PlaceLimitOrder>,
-        side: Side,
         price: u64,
         size: u64,
         expire_in: u64,
+        order_type: OrderType,
+        client_order_id: u64,
+        self_trade_behavior: SelfTradeBehavior,
     ) -> Result<()> {
-        instructions::place_limit_order(ctx, side, price, size, expire_in)
+        instructions::place_synthetic_ask(
+            ctx,
+            price,
+            size,
+            expire_in,
+            order_type,
+            client_order_id,
+            self_trade_behavior,
+        )
     }
 
     pub fn cancel_limit_order(
@@ -49,12 +147,43 @@ pub mod ember_markets {
         instructions::cancel_limit_order(ctx, side, order_id)
     }
 
+    pub fn cancel_order_by_client_id(
+        ctx: Context<CancelLimitOrder>,
+        side: Side,
+        client_order_id: u64,
+    ) -> Result<()> {
+        instructions::cancel_order_by_client_id(ctx, side, client_order_id)
+    }
+
+    pub fn cancel_all_orders(ctx: Context<CancelAllOrders>, side: Option<Side>) -> Result<()> {
+        instructions::cancel_all_orders(ctx, side)
+    }
+
     pub fn place_market_order(
         ctx: Context<PlaceMarketOrder>,
         side: Side,
         amount: u64,
-    ) -> Result<()> {
-        instructions::place_market_order(ctx, side, amount)
+        worst_price: u64,
+        min_fill: u64,
+        self_trade_behavior: SelfTradeBehavior,
+    ) -> Result<MarketOrderResult> {
+        instructions::place_market_order(ctx, side, amount, worst_price, min_fill, self_trade_behavior)
+    }
+
+    pub fn place_market_bid_by_quote(
+        ctx: Context<PlaceMarketOrder>,
+        quote_budget: u64,
+        worst_price: u64,
+        min_fill: u64,
+        self_trade_behavior: SelfTradeBehavior,
+    ) -> Result<MarketOrderResult> {
+        instructions::place_market_bid_by_quote(
+            ctx,
+            quote_budget,
+            worst_price,
+            min_fill,
+            self_trade_behavior,
+        )
     }
 
     pub fn claim_balance(ctx: Context<ClaimBalance>) -> Result<()> {
@@ -73,4 +202,39 @@ pub mod ember_markets {
     pub fn clear_expired_orders(ctx: Context<ClearExpiredOrders>) -> Result<()> {
         instructions::clear_expired_orders(ctx)
     }
+
+    pub fn sample_rewards(ctx: Context<SampleRewards>) -> Result<()> {
+        instructions::sample_rewards(ctx)
+    }
+
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        instructions::claim_rewards(ctx)
+    }
+
+    pub fn close_market(ctx: Context<CloseMarket>) -> Result<()> {
+        instructions::close_market(ctx)
+    }
+
+    pub fn set_market_paused(ctx: Context<SetMarketPaused>, paused: bool) -> Result<()> {
+        instructions::set_market_paused(ctx, paused)
+    }
+
+    pub fn sync_resolution(ctx: Context<SyncResolution>) -> Result<()> {
+        instructions::sync_resolution(ctx)
+    }
+
+    pub fn resolve_market(ctx: Context<ResolveMarket>, outcome: u64) -> Result<()> {
+        instructions::resolve_market(ctx, outcome)
+    }
+
+    pub fn settle_market(ctx: Context<SettleMarket>, limit: u64) -> Result<()> {
+        instructions::settle_market(ctx, limit)
+    }
+
+    pub fn consume_events<'info>(
+        ctx: Context<'_, '_, '_, 'info, ConsumeEvents<'info>>,
+        limit: u64,
+    ) -> Result<()> {
+        instructions::consume_events(ctx, limit)
+    }
 }
@@ -0,0 +1,112 @@
+use anchor_lang::prelude::*;
+
+use crate::consts::EVENT_QUEUE_SIZE;
+use crate::ember_errors::EmberErr;
+use crate::state::orderbook::Order;
+use crate::state::side::{Side, StoredSide};
+
+pub const EVENT_FILL: u64 = 0;
+pub const EVENT_OUT: u64 = 1;
+
+// matching doesn't touch the makers' balances, every fill and every maker order removed by the
+// matching engine (expired, evicted, cancelled by self trade prevention) is pushed here and
+// settled later by the consume_events crank. the queue also serves as the record of the fills
+#[account(zero_copy)]
+pub struct EventQueue {
+    pub head: u64,    // index of the oldest event
+    pub count: u64,   // events waiting to be consumed
+    pub seq_num: u64, // sequence number of the next event pushed, never reset
+    pub events: [Event; EVENT_QUEUE_SIZE],
+}
+
+#[zero_copy]
+pub struct Event {
+    pub event_type: u64, // EVENT_FILL or EVENT_OUT
+    pub side: StoredSide, // side of the maker order
+    pub maker_uid: u64,
+    pub taker_uid: u64, // user whose instruction generated the event
+    pub order_id: u64,
+    pub client_order_id: u64,
+    pub price: u64, // price of the maker order
+    pub size: u64,  // size filled for fills, size taken out of the order for outs
+    pub timestamp: u64,
+    pub base_token: u64, // balance token index of the outcome traded, 1 or 2
+    pub seq_num: u64,
+    pub maker_fee: u64,    // fills only, quote charged to the maker
+    pub maker_rebate: u64, // fills only, quote paid to the maker
+    pub fee_unlocked: u64, // bids only, quote of the order's fee lock given back to the maker
+}
+
+impl Event {
+    pub fn fill(maker_side: Side, order_id: u64, order: &Order, taker_uid: u64, size: u64, base_token: u8) -> Result<Self> {
+        Self::new(EVENT_FILL, maker_side, order_id, order, taker_uid, size, base_token)
+    }
+
+    pub fn with_maker_fees(self, maker_fee: u64, maker_rebate: u64, fee_unlocked: u64) -> Self {
+        Self {
+            maker_fee,
+            maker_rebate,
+            fee_unlocked,
+            ..self
+        }
+    }
+
+    pub fn out(maker_side: Side, order_id: u64, order: &Order, taker_uid: u64, base_token: u8) -> Result<Self> {
+        Self::new(EVENT_OUT, maker_side, order_id, order, taker_uid, order.size, base_token)
+    }
+
+    fn new(
+        event_type: u64,
+        maker_side: Side,
+        order_id: u64,
+        order: &Order,
+        taker_uid: u64,
+        size: u64,
+        base_token: u8,
+    ) -> Result<Self> {
+        Ok(Self {
+            event_type,
+            side: maker_side.into(),
+            maker_uid: order.uid,
+            taker_uid,
+            order_id,
+            client_order_id: order.client_order_id,
+            price: order.price,
+            size,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+            base_token: base_token as u64,
+            seq_num: 0,
+            maker_fee: 0,
+            maker_rebate: 0,
+            fee_unlocked: order.fee_locked,
+        })
+    }
+}
+
+impl EventQueue {
+    pub fn push(&mut self, mut event: Event) -> Result<()> {
+        if self.count as usize == EVENT_QUEUE_SIZE {
+            return err!(EmberErr::EventQueueFull);
+        }
+        event.seq_num = self.seq_num;
+        let idx = (self.head + self.count) as usize % EVENT_QUEUE_SIZE;
+        self.events[idx] = event;
+        self.count += 1;
+        self.seq_num += 1;
+        Ok(())
+    }
+
+    pub fn peek(&self) -> Option<&Event> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(&self.events[self.head as usize])
+    }
+
+    pub fn pop(&mut self) -> Option<Event> {
+        let event = *self.peek()?;
+        self.head = (self.head + 1) % EVENT_QUEUE_SIZE as u64;
+        self.count -= 1;
+        Some(event)
+    }
+}
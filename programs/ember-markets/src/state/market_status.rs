@@ -0,0 +1,12 @@
+use anchor_lang::prelude::*;
+
+// a market trades while Open, stops at its end time and is Resolved once its condition is
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MarketStatus {
+    Open = 0,
+    // end time reached, no more orders, cancels and claims still work
+    Closed = 1,
+    // the condition resolved, winning_outcome is set
+    Resolved = 2,
+}
@@ -1,4 +1,7 @@
 pub mod side;
+pub mod order_type;
+pub mod market_status;
+pub mod event_queue;
 pub mod orderbook;
 pub mod state;
 
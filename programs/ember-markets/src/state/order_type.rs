@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OrderType {
+    // takes whatever crosses, the rest rests on the book
+    Limit = 0,
+    // only rests on the book, fails if it would take
+    PostOnly = 1,
+    // only rests on the book, a crossing price slides to one tick behind the best opposite order
+    PostOnlySlide = 2,
+    // takes whatever crosses, the rest is cancelled, fails if nothing fills
+    ImmediateOrCancel = 3,
+    // takes the whole size or fails
+    FillOrKill = 4,
+}
+
+// what happens when an incoming order meets a resting order of the same user
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SelfTradeBehavior {
+    // the rest of the incoming order is cancelled
+    CancelTaker = 0,
+    // the resting order is cancelled and matching goes on
+    CancelMaker = 1,
+    // both orders are reduced by the overlapping size, nothing trades
+    DecrementBoth = 2,
+    // the transaction fails
+    Abort = 3,
+}
+
+impl OrderType {
+    pub fn can_take(&self) -> bool {
+        !matches!(self, OrderType::PostOnly | OrderType::PostOnlySlide)
+    }
+
+    pub fn can_rest(&self) -> bool {
+        !matches!(self, OrderType::ImmediateOrCancel | OrderType::FillOrKill)
+    }
+}
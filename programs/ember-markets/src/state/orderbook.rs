@@ -1,8 +1,18 @@
 use anchor_lang::prelude::*;
 
-use crate::consts::ORDER_BOOK_SIZE;
+use crate::consts::{BPS_DENOMINATOR, MAX_ORDER_PRICE, ORDER_BOOK_NODES, ORDER_BOOK_SIZE};
 use crate::ember_errors::EmberErr;
-use crate::state::side::{Sides, StoredSide};
+use crate::state::order_type::{OrderType, SelfTradeBehavior};
+use crate::state::side::{Side, Sides, StoredSide};
+use crate::state::state::{maker_fee, maker_fee_lock, quote_lots, taker_fee};
+use crate::state::event_queue::{Event, EventQueue};
+
+// null pointer of the slab, every node index including 0 is a valid node
+pub const NIL: u64 = u64::MAX;
+
+pub const NODE_FREE: u64 = 0;
+pub const NODE_INNER: u64 = 1;
+pub const NODE_LEAF: u64 = 2;
 
 #[account(zero_copy)]
 pub struct OrderBookState {
@@ -11,12 +21,30 @@ pub struct OrderBookState {
     pub base_mint: Pubkey,
 }
 
+// each side of the book is a critbit tree over the order ids, stored in a slab of nodes.
+// an order id is the price in the upper 32 bits and the placement sequence in the lower 32 bits,
+// inverted for bids, so the best order is the max id for bids and the min id for asks,
+// and within a price the oldest order comes first
 #[zero_copy]
 pub struct OrderBook {
     pub side: StoredSide,
-    pub best_order_idx: u64,
-    pub worst_order_idx: u64,
-    pub orders: [Order; ORDER_BOOK_SIZE],
+    pub root: u64,
+    pub best_order_idx: u64, // leaf of the best order, kept up to date on every insert and removal
+    pub free_list_head: u64, // released nodes, linked through children[0]
+    pub bump_index: u64,     // nodes from this index on were never handed out
+    pub leaf_count: u64,
+    pub next_seq: u64,
+    pub nodes: [Node; ORDER_BOOK_NODES],
+}
+
+#[zero_copy]
+#[derive(Default)]
+pub struct Node {
+    pub tag: u64,
+    pub prefix_len: u64,    // inner nodes: index of the critical bit, keys below share the bits before it
+    pub key: u64,           // inner nodes: any key below it, leaves: the order id
+    pub children: [u64; 2], // inner nodes: children by critical bit, free nodes: next free node
+    pub order: Order,       // leaves only
 }
 
 #[zero_copy]
@@ -25,42 +53,453 @@ pub struct Order {
     pub price: u64,
     pub size: u64,
     pub uid: u64,
-    pub prev: u64,
-    pub next: u64,
     pub expire_at: u64,
+    pub client_order_id: u64, // set by the user at placement, lets bots track their orders without knowing the id
+    pub maker_fee_bps: i64,   // maker fee at placement, kept so fee changes don't touch what the order locked
+    pub fee_locked: u64,      // bids only, quote still locked for the maker fee
+    pub placed_at: u64,
+}
+
+impl Order {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expire_at != 0 && self.expire_at < now
+    }
+
+    // part of the fee lock released when size of the order leaves the book. partial releases round
+    // down like the lock did, so whatever they leave over is released with the last of the order
+    pub fn fee_release(&self, size: u64, tick_size: u64) -> Result<u64> {
+        if size == self.size {
+            return Ok(self.fee_locked);
+        }
+        let release = maker_fee_lock(quote_lots(size, self.price, tick_size)?, self.maker_fee_bps);
+        Ok(std::cmp::min(release, self.fee_locked))
+    }
+}
+
+// an incoming order taking from the book, limit_price in ticks bounds the fills,
+// u64::MAX for a bid or 0 for an ask takes any price. a bid can also be bounded by the quote lots
+// it spends with max_quote, taker fee included, u64::MAX when it's only sized in base lots
+pub struct TakerOrder {
+    pub side: Side,
+    pub limit_price: u64,
+    pub size: u64,
+    pub max_quote: u64,
+    pub uid: u64,
+    pub self_trade_behavior: SelfTradeBehavior,
+    pub taker_fee_bps: u64,
+}
+
+// what an incoming order took from the book in base lots, quote_amount is the quote lots paid at the makers' prices.
+// decremented is the size removed by self trade prevention without trading, and taker_cancelled
+// is set when self trade prevention cancelled the rest of the incoming order. complement_filled is
+// the part of filled taken from the complement, that many outcome sets have to be minted or merged.
+// taker_fee is paid by the taker on top of quote_amount, fees is what goes to the fee vault, the
+// taker and maker fees less the maker rebates
+#[derive(Default)]
+pub struct FillResult {
+    pub filled: u64,
+    pub quote_amount: u64,
+    pub complement_filled: u64,
+    pub taker_fee: u64,
+    pub fees: u64,
+    pub decremented: u64,
+    pub taker_cancelled: bool,
+}
+
+// the same side of the market's other outcome book. a bid there at price q wants the other outcome, so
+// together with a bid here at max_price - q it pays for a whole outcome set that gets minted, and asks
+// are matched the same way by merging the sets they offer
+pub struct Complement<'a> {
+    pub book: &'a mut OrderBook,
+    pub base_token: u8,
+    pub max_price: u64,
+}
+
+impl Complement<'_> {
+    // best price the complement offers an order of this book, in this book's terms
+    pub fn best_price(&self) -> Option<u64> {
+        self.book.best_order().map(|(_, order)| self.max_price - order.price)
+    }
+}
+
+// fill of a market order, returned to the caller through return data. amounts are in lots like the balances
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct MarketOrderResult {
+    pub filled: u64,
+    pub quote_amount: u64,
+    pub average_price: u64, // in ticks, rounded down
+    pub unspent_quote: u64, // part of the quote budget that wasn't spent, 0 for orders sized in base
+    pub taker_fee: u64,     // paid on top of quote_amount for bids, taken out of it for asks
+}
+
+impl MarketOrderResult {
+    pub fn new(fill: &FillResult, quote_budget: Option<u64>, tick_size: u64) -> Self {
+        Self {
+            filled: fill.filled,
+            quote_amount: fill.quote_amount,
+            average_price: fill.quote_amount.checked_div(fill.filled * tick_size).unwrap_or(0),
+            unspent_quote: quote_budget.map_or(0, |budget| budget - fill.quote_amount - fill.taker_fee),
+            taker_fee: fill.taker_fee,
+        }
+    }
+}
+
+impl OrderBookState {
+    // matches an incoming order against the other side of the book at the resting orders' prices,
+    // best price first and oldest first within a price. with a complement, the same side of the other
+    // outcome book competes on its implied prices and ties go to this book. fills are pushed to the event
+    // queue for the makers to be settled by the crank, so are the expired orders met on the way, which get
+    // removed. orders of the taker itself are handled according to its self trade behavior
+    pub fn match_order(
+        &mut self,
+        taker: &TakerOrder,
+        event_queue: &mut EventQueue,
+        base_token: u8,
+        tick_size: u64,
+        mut complement: Option<Complement>,
+    ) -> Result<FillResult> {
+        let side = taker.side;
+        let size = taker.size;
+        let now = Clock::get()?.unix_timestamp as u64;
+        let (book, maker_side) = match side {
+            Side::Bid => (&mut self.asks, Side::Ask),
+            Side::Ask => (&mut self.bids, Side::Bid),
+        };
+
+        let mut result = FillResult::default();
+        while result.filled + result.decremented < size {
+            let direct_price = book.best_order().map(|(_, order)| order.price);
+            let implied_price = complement.as_ref().and_then(|complement| complement.best_price());
+            let from_complement = match (direct_price, implied_price) {
+                (_, None) => false,
+                (None, Some(_)) => true,
+                (Some(direct), Some(implied)) => match side {
+                    Side::Bid => implied < direct,
+                    Side::Ask => implied > direct,
+                },
+            };
+            // the book the order comes from, the side and token its maker trades, and the price the taker gets
+            let (source, order_side, order_base_token, complement_max_price) = if from_complement {
+                let complement = complement.as_mut().unwrap();
+                let max_price = complement.max_price;
+                (&mut *complement.book, side, complement.base_token, Some(max_price))
+            } else {
+                (&mut *book, maker_side, base_token, None)
+            };
+            let (order_id, order) = match source.best_order() {
+                Some(best) => best,
+                None => break,
+            };
+            let price = complement_max_price.map_or(order.price, |max_price| max_price - order.price);
+
+            if order.is_expired(now) {
+                event_queue.push(Event::out(order_side, order_id, &order, taker.uid, order_base_token)?)?;
+                source.remove_order(order_id);
+                continue;
+            }
+
+            let crosses = match side {
+                Side::Bid => price <= taker.limit_price,
+                Side::Ask => price >= taker.limit_price,
+            };
+            if !crosses {
+                break;
+            }
+
+            let remaining = size - result.filled - result.decremented;
+            if order.uid == taker.uid {
+                match taker.self_trade_behavior {
+                    SelfTradeBehavior::Abort => return err!(EmberErr::SelfTrade),
+                    SelfTradeBehavior::CancelTaker => {
+                        result.taker_cancelled = true;
+                        break;
+                    }
+                    SelfTradeBehavior::CancelMaker => {
+                        event_queue.push(Event::out(order_side, order_id, &order, taker.uid, order_base_token)?)?;
+                        source.remove_order(order_id);
+                    }
+                    SelfTradeBehavior::DecrementBoth => {
+                        let decrement = std::cmp::min(order.size, remaining);
+                        let fee_unlocked = order.fee_release(decrement, tick_size)?;
+                        let unlocked = Order {
+                            size: decrement,
+                            fee_locked: fee_unlocked,
+                            ..order
+                        };
+                        event_queue.push(Event::out(order_side, order_id, &unlocked, taker.uid, order_base_token)?)?;
+                        result.decremented += decrement;
+                        if decrement == order.size {
+                            source.remove_order(order_id);
+                        } else {
+                            let resting = &mut source.nodes[source.best_order_idx as usize].order;
+                            resting.size -= decrement;
+                            resting.fee_locked -= fee_unlocked;
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // a quote bounded bid only takes what the rest of its budget can pay for, fee included
+            let affordable = if taker.max_quote == u64::MAX {
+                u64::MAX
+            } else {
+                let budget = taker.max_quote - result.quote_amount - result.taker_fee;
+                affordable_size(budget, quote_lots(1, price, tick_size)?, taker.taker_fee_bps)
+            };
+            let amount_to_fill = std::cmp::min(std::cmp::min(order.size, remaining), affordable);
+            if amount_to_fill == 0 {
+                break;
+            }
+            let quote = quote_lots(amount_to_fill, price, tick_size)?;
+            let fill_taker_fee = taker_fee(quote, taker.taker_fee_bps);
+            // the maker's fee is on its own side of the trade, for a complement that's its own price
+            let (fill_maker_fee, maker_rebate) =
+                maker_fee(quote_lots(amount_to_fill, order.price, tick_size)?, order.maker_fee_bps);
+            let fill_maker_rebate = std::cmp::min(maker_rebate, fill_taker_fee);
+            // a bid's maker fee comes out of its fee lock, the rest of what the fill releases goes back
+            let fee_release = order.fee_release(amount_to_fill, tick_size)?;
+            result.filled += amount_to_fill;
+            result.quote_amount += quote;
+            result.taker_fee += fill_taker_fee;
+            result.fees += fill_taker_fee + fill_maker_fee - fill_maker_rebate;
+            if from_complement {
+                result.complement_filled += amount_to_fill;
+            }
+            // the maker receives the other leg of the trade when the event is consumed
+            let event = Event::fill(order_side, order_id, &order, taker.uid, amount_to_fill, order_base_token)?;
+            event_queue.push(event.with_maker_fees(
+                fill_maker_fee,
+                fill_maker_rebate,
+                fee_release.saturating_sub(fill_maker_fee),
+            ))?;
+
+            if amount_to_fill == order.size {
+                source.remove_order(order_id);
+            } else {
+                let resting = &mut source.nodes[source.best_order_idx as usize].order;
+                resting.size -= amount_to_fill;
+                resting.fee_locked -= fee_release;
+            }
+        }
+        Ok(result)
+    }
+
+    // price of the best order an incoming order of the given side would trade against
+    pub fn best_opposite_price(&self, side: Side) -> Option<u64> {
+        let book = match side {
+            Side::Bid => &self.asks,
+            Side::Ask => &self.bids,
+        };
+        book.best_order().map(|(_, order)| order.price)
+    }
+
+    // best bid and ask of the book counting the implied prices of the other outcome book
+    pub fn best_prices(&self, other: &OrderBookState, max_price: u64) -> Option<(u64, u64)> {
+        let price = |book: &OrderBook| book.best_order().map(|(_, order)| order.price);
+        let bid = [price(&self.bids), price(&other.asks).map(|price| max_price - price)]
+            .into_iter()
+            .flatten()
+            .max()?;
+        let ask = [price(&self.asks), price(&other.bids).map(|price| max_price - price)]
+            .into_iter()
+            .flatten()
+            .min()?;
+        Some((bid, ask))
+    }
+
+    // post only orders must not take, PostOnly fails on a crossing price and PostOnlySlide
+    // moves it one tick behind the best opposite order, counting the complement's implied prices.
+    // other order types keep their price
+    pub fn post_only_price(
+        &self,
+        side: Side,
+        price: u64,
+        order_type: OrderType,
+        complement: Option<&Complement>,
+    ) -> Result<u64> {
+        let direct = self.best_opposite_price(side);
+        let implied = complement.and_then(|complement| complement.best_price());
+        let best = match (direct, implied) {
+            (Some(direct), Some(implied)) => match side {
+                Side::Bid => std::cmp::min(direct, implied),
+                Side::Ask => std::cmp::max(direct, implied),
+            },
+            (Some(best), None) | (None, Some(best)) => best,
+            (None, None) => return Ok(price),
+        };
+        let crosses = match side {
+            Side::Bid => price >= best,
+            Side::Ask => price <= best,
+        };
+        if !crosses {
+            return Ok(price);
+        }
+
+        match order_type {
+            OrderType::PostOnly => err!(EmberErr::PriceCrossesTheSpread),
+            OrderType::PostOnlySlide => {
+                let slid = match side {
+                    Side::Bid => best - 1,
+                    Side::Ask => best + 1,
+                };
+                if slid == 0 || slid > MAX_ORDER_PRICE {
+                    return err!(EmberErr::PostOnlySlideNoPrice);
+                }
+                Ok(slid)
+            }
+            _ => Ok(price),
+        }
+    }
 }
 
 impl OrderBook {
-    pub fn insert_order(&mut self, size: u64, price: u64, uid: u64, expire_in: u64) -> Result<()> {
-        let mut order = Order::default();
-        order.uid = uid;
-        order.size = size;
-        order.price = price;
-        order.expire_at = expire_in + Clock::get()?.unix_timestamp as u64;
-
-        let mut prev_index: Option<u64> = Some(0);
-        for i in 0..self.orders.len() {
-            if self.is_price_better(price, self.orders[i].price) {
-                let order_idx = self.get_empty_node().unwrap_or_else(|| {
-                    let i = self.worst_order_idx;
-                    self.remove_order(i);
-                    i
-                });
-
-                order.prev = prev_index.unwrap_or(0);
-
-                order.next = i as u64;
-
-                self.place_order(order.clone(), order_idx);
+    pub fn init(&mut self, side: Side) {
+        self.side = side.into();
+        self.root = NIL;
+        self.best_order_idx = NIL;
+        self.free_list_head = NIL;
+        self.bump_index = 0;
+        self.leaf_count = 0;
+        self.next_seq = 0;
+    }
+
+    // inserts the order and returns its id. when the book is full the worst order gets evicted
+    // and returned with its id so the caller can refund it
+    pub fn insert_order(&mut self, mut order: Order, expire_in: u64) -> Result<(u64, Option<(u64, Order)>)> {
+        let price = order.price;
+        if price == 0 || price > MAX_ORDER_PRICE {
+            return err!(EmberErr::InvalidPrice);
+        }
+
+        order.placed_at = Clock::get()?.unix_timestamp as u64;
+        if expire_in != 0 {
+            order.expire_at = expire_in + order.placed_at;
+        }
+
+        let order_id = self.order_id(price, self.next_seq);
+        self.next_seq += 1;
+
+        let mut evicted = None;
+        if self.leaf_count as usize >= ORDER_BOOK_SIZE {
+            // if order's price is the worst, there's nothing to evict for it
+            let worst_idx = self.worst_leaf();
+            let worst = self.nodes[worst_idx as usize];
+            if !self.is_price_better(price, worst.order.price) {
+                return err!(EmberErr::OrderBookFull);
             }
-            prev_index = Some(i as u64);
+            evicted = self.remove_order(worst.key).map(|order| (worst.key, order));
         }
-        // if order's price is the worst, insert it if there's an empty node
-        let order_idx = self.get_empty_node().ok_or(EmberErr::OrderBookFull)?;
-        order.prev = prev_index.unwrap_or(0);
-        order.next = 0;
-        self.place_order(order, order_idx);
-        Ok(())
+
+        self.insert_leaf(order_id, order)?;
+        Ok((order_id, evicted))
+    }
+
+    pub fn remove_order(&mut self, order_id: u64) -> Option<Order> {
+        if self.root == NIL {
+            return None;
+        }
+
+        let mut grandparent = NIL;
+        let mut grandparent_dir = 0;
+        let mut parent = NIL;
+        let mut parent_dir = 0;
+        let mut current = self.root;
+        while self.nodes[current as usize].tag == NODE_INNER {
+            let node = self.nodes[current as usize];
+            grandparent = parent;
+            grandparent_dir = parent_dir;
+            parent = current;
+            parent_dir = critical_bit(order_id, node.prefix_len);
+            current = node.children[parent_dir];
+        }
+        if self.nodes[current as usize].key != order_id {
+            return None;
+        }
+        let order = self.nodes[current as usize].order;
+
+        // the sibling takes the parent's place
+        if parent == NIL {
+            self.root = NIL;
+        } else {
+            let sibling = self.nodes[parent as usize].children[1 - parent_dir];
+            if grandparent == NIL {
+                self.root = sibling;
+            } else {
+                self.nodes[grandparent as usize].children[grandparent_dir] = sibling;
+            }
+            self.free_node(parent);
+        }
+        self.free_node(current);
+        self.leaf_count -= 1;
+
+        if self.best_order_idx == current {
+            self.best_order_idx = self.best_leaf();
+        }
+        Some(order)
+    }
+
+    pub fn find_order(&self, order_id: u64) -> Option<u64> {
+        if self.root == NIL {
+            return None;
+        }
+        let mut current = self.root;
+        while self.nodes[current as usize].tag == NODE_INNER {
+            let node = &self.nodes[current as usize];
+            current = node.children[critical_bit(order_id, node.prefix_len)];
+        }
+        if self.nodes[current as usize].key == order_id {
+            Some(current)
+        } else {
+            None
+        }
+    }
+
+    pub fn best_order(&self) -> Option<(u64, Order)> {
+        if self.best_order_idx == NIL {
+            return None;
+        }
+        let node = &self.nodes[self.best_order_idx as usize];
+        Some((node.key, node.order))
+    }
+
+    // removes the orders of a user, all of them or only the ones with the given client order id
+    pub fn remove_user_orders(&mut self, uid: u64, client_order_id: Option<u64>) -> Vec<Order> {
+        let order_ids: Vec<u64> = self
+            .orders()
+            .filter(|(_, order)| {
+                order.uid == uid
+                    && (client_order_id.is_none() || client_order_id == Some(order.client_order_id))
+            })
+            .map(|(order_id, _)| order_id)
+            .collect();
+        order_ids
+            .into_iter()
+            .filter_map(|order_id| self.remove_order(order_id))
+            .collect()
+    }
+
+    // every resting order with its id, in slab order
+    pub fn orders(&self) -> impl Iterator<Item = (u64, &Order)> {
+        self.nodes[..self.bump_index as usize]
+            .iter()
+            .filter(|node| node.tag == NODE_LEAF)
+            .map(|node| (node.key, &node.order))
+    }
+
+    // resting orders with their ids from the best one outward, a caller stopping early
+    // only walks the part of the tree holding the orders it took
+    pub fn orders_by_priority(&self) -> OrdersByPriority<'_> {
+        let stack = if self.root == NIL { Vec::new() } else { vec![self.root] };
+        OrdersByPriority { book: self, stack }
+    }
+
+    fn order_id(&self, price: u64, seq: u64) -> u64 {
+        let seq = match self.side.into() {
+            Sides::Bid => !seq,
+            Sides::Ask => seq,
+        };
+        (price << 32) | (seq & u32::MAX as u64)
     }
 
     fn is_price_better(&self, lhs: u64, rhs: u64) -> bool {
@@ -70,46 +509,167 @@ impl OrderBook {
         }
     }
 
-    fn get_empty_node(&self) -> Option<u64> {
-        for i in 0..self.orders.len() {
-            if self.orders[i].uid == 0 {
-                return Some(i as u64);
+    fn insert_leaf(&mut self, key: u64, order: Order) -> Result<()> {
+        let leaf = self.alloc_node().ok_or(EmberErr::OrderBookFull)?;
+        self.nodes[leaf as usize] = Node {
+            tag: NODE_LEAF,
+            prefix_len: 64,
+            key,
+            children: [NIL, NIL],
+            order,
+        };
+
+        if self.root == NIL {
+            self.root = leaf;
+        } else {
+            // the closest key decides where the new critical bit is
+            let mut current = self.root;
+            while self.nodes[current as usize].tag == NODE_INNER {
+                let node = &self.nodes[current as usize];
+                current = node.children[critical_bit(key, node.prefix_len)];
+            }
+            let shared = (self.nodes[current as usize].key ^ key).leading_zeros() as u64;
+            if shared == 64 {
+                self.free_node(leaf);
+                return err!(EmberErr::DuplicateOrderId);
+            }
+
+            // walk down to the first node that doesn't share the new critical bit and split it
+            let mut parent = NIL;
+            let mut parent_dir = 0;
+            let mut current = self.root;
+            while self.nodes[current as usize].tag == NODE_INNER
+                && self.nodes[current as usize].prefix_len < shared
+            {
+                parent = current;
+                parent_dir = critical_bit(key, self.nodes[current as usize].prefix_len);
+                current = self.nodes[current as usize].children[parent_dir];
+            }
+
+            let inner = self.alloc_node().ok_or(EmberErr::OrderBookFull)?;
+            let dir = critical_bit(key, shared);
+            let mut children = [NIL, NIL];
+            children[dir] = leaf;
+            children[1 - dir] = current;
+            self.nodes[inner as usize] = Node {
+                tag: NODE_INNER,
+                prefix_len: shared,
+                key,
+                children,
+                order: Order::default(),
+            };
+            if parent == NIL {
+                self.root = inner;
+            } else {
+                self.nodes[parent as usize].children[parent_dir] = inner;
             }
         }
-        None
+
+        self.leaf_count += 1;
+        let is_best = match self.best_order() {
+            Some((best_id, _)) => match self.side.into() {
+                Sides::Bid => key > best_id,
+                Sides::Ask => key < best_id,
+            },
+            None => true,
+        };
+        if is_best {
+            self.best_order_idx = leaf;
+        }
+        Ok(())
     }
 
-    fn place_order(&mut self, order: Order, i: u64) {
-        assert_eq!(self.orders[i as usize].uid, 0);
+    fn best_leaf(&self) -> u64 {
+        match self.side.into() {
+            Sides::Bid => self.edge_leaf(1),
+            Sides::Ask => self.edge_leaf(0),
+        }
+    }
 
-        if order.prev == 0 {
-            self.best_order_idx = i;
-        } else {
-            self.orders[order.prev as usize].prev = i;
+    fn worst_leaf(&self) -> u64 {
+        match self.side.into() {
+            Sides::Bid => self.edge_leaf(0),
+            Sides::Ask => self.edge_leaf(1),
         }
+    }
 
-        if order.next == 0 {
-            self.worst_order_idx = i;
-        } else {
-            self.orders[order.next as usize].prev = i;
+    // leaf with the min (dir 0) or max (dir 1) key
+    fn edge_leaf(&self, dir: usize) -> u64 {
+        if self.root == NIL {
+            return NIL;
+        }
+        let mut current = self.root;
+        while self.nodes[current as usize].tag == NODE_INNER {
+            current = self.nodes[current as usize].children[dir];
         }
-        self.orders[i as usize] = order;
+        current
     }
 
-    pub fn remove_order(&mut self, i: u64) {
-        let order: Order = self.orders[i as usize].clone();
-        if order.prev == 0 {
-            self.best_order_idx = order.next;
-        } else {
-            let to_remove_order = self.orders.get_mut(order.prev as usize).unwrap();
-            to_remove_order.next = order.next;
+    fn alloc_node(&mut self) -> Option<u64> {
+        if self.free_list_head != NIL {
+            let i = self.free_list_head;
+            self.free_list_head = self.nodes[i as usize].children[0];
+            return Some(i);
+        }
+        if (self.bump_index as usize) < self.nodes.len() {
+            self.bump_index += 1;
+            return Some(self.bump_index - 1);
         }
+        None
+    }
 
-        if order.next == 0 {
-            self.worst_order_idx = order.prev;
-        } else {
-            self.orders[order.next as usize].prev = order.prev;
+    fn free_node(&mut self, i: u64) {
+        self.nodes[i as usize] = Node {
+            tag: NODE_FREE,
+            children: [self.free_list_head, NIL],
+            ..Node::default()
+        };
+        self.free_list_head = i;
+    }
+}
+
+pub struct OrdersByPriority<'a> {
+    book: &'a OrderBook,
+    stack: Vec<u64>,
+}
+
+impl<'a> Iterator for OrdersByPriority<'a> {
+    type Item = (u64, &'a Order);
+
+    // depth first, the child holding the better keys is pushed last so it's visited first
+    fn next(&mut self) -> Option<Self::Item> {
+        let (better, worse) = match self.book.side.into() {
+            Sides::Bid => (1, 0),
+            Sides::Ask => (0, 1),
+        };
+        while let Some(current) = self.stack.pop() {
+            let node = &self.book.nodes[current as usize];
+            if node.tag == NODE_LEAF {
+                return Some((node.key, &node.order));
+            }
+            self.stack.push(node.children[worse]);
+            self.stack.push(node.children[better]);
         }
-        self.orders[i as usize] = Order::default();
+        None
+    }
+}
+
+// largest size whose quote at unit quote lots per base lot plus the taker fee fits in the budget
+fn affordable_size(budget: u64, unit: u64, taker_fee_bps: u64) -> u64 {
+    let cost = |size: u64| {
+        let quote = size * unit;
+        quote as u128 + taker_fee(quote, taker_fee_bps) as u128
+    };
+    let with_fee = unit as u128 * (BPS_DENOMINATOR + taker_fee_bps) as u128;
+    let mut size = (budget as u128 * BPS_DENOMINATOR as u128 / with_fee) as u64;
+    // the fee is rounded up, so the estimate can be a lot over
+    while size > 0 && cost(size) > budget as u128 {
+        size -= 1;
     }
+    size
+}
+
+// bit of the key at the given index, counting from the most significant bit
+fn critical_bit(key: u64, index: u64) -> usize {
+    ((key >> (63 - index)) & 1) as usize
 }
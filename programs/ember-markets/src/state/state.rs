@@ -1,7 +1,14 @@
 use anchor_lang::prelude::*;
 
-use crate::consts::USERS_BALANCES;
+use crate::consts::{
+    ALL_ROLES, BPS_DENOMINATOR, CREATOR_BOND, MAX_ADMINS, MAX_CREATOR_FEE_BPS, MAX_FEE_TIERS, MAX_ORDER_PRICE,
+    MAX_QUESTION_LENGTH, MIN_MARKET_DURATION, ROLE_ADMIN, USERS_BALANCES, VOLUME_PERIOD,
+};
 use crate::ember_errors::EmberErr;
+use crate::state::event_queue::{Event, EVENT_FILL};
+use crate::state::market_status::MarketStatus;
+use crate::state::orderbook::Order;
+use crate::state::side::Side;
 
 #[account]
 pub struct Market {
@@ -13,6 +20,7 @@ pub struct Market {
     pub orderbook_state_1: Pubkey,
     pub orderbook_state_2: Pubkey,
     pub balances: Pubkey,
+    pub event_queue: Pubkey,
     pub resolved: bool,
     pub quote_key: Pubkey,
     pub outcome_1_key: Pubkey,
@@ -20,9 +28,93 @@ pub struct Market {
     pub base_vault_1: Pubkey,
     pub base_vault_2: Pubkey,
     pub quote_vault: Pubkey,
+    pub ticket_vault: Pubkey, // holds the tickets while outcome sets are minted or merged
+    // balances and order sizes are in lots, base_lot_size and quote_lot_size are the native token
+    // amounts of one lot. prices are in ticks of tick_size quote lots per base lot and must be
+    // above 0 and below max_price, the price in ticks at which an outcome is worth a whole ticket payout
+    pub tick_size: u64,
+    pub base_lot_size: u64,
+    pub quote_lot_size: u64,
+    pub max_price: u64,
+    // fees in basis points of the quote traded. a negative maker fee is a rebate paid out of the taker fee,
+    // fees accrue in fee_vault, owned by the market auth pda
+    pub maker_fee_bps: i64,
+    pub taker_fee_bps: u64,
+    pub fee_vault: Pubkey,
+    // liquidity rewards for resting orders within rewards_max_spread ticks of the midpoint, paid out of
+    // reward_vault. 0 rewards_multiplier means the market has no rewards
+    pub reward_vault: Pubkey,
+    pub rewards_max_spread: u64,
+    pub last_rewards_sample: u64,
+    // share of the taker fees of referred users that goes to their referrer
+    pub referral_share_bps: u64,
+    pub status: MarketStatus,
+    pub winning_outcome: u64, // balance token index of the winning outcome once resolved, 1 or 2
+    pub settled: bool,        // balances converted to quote after resolution, only quote is left to claim
+    pub settled_users: u64,   // settlement cursor, the users up to this uid had their balances converted
+    // quote bond posted by the creator in bond_vault, refunded once the market is settled or slashed by admins.
+    // the creator earns creator_fee_bps of the fees withdrawn, creator_fees is what it can claim from the fee vault
+    pub bond: u64,
+    pub bond_vault: Pubkey,
+    pub creator_fee_bps: u64,
+    pub creator_fees: u64,
+    pub paused: bool, // set by pausers, no trading until unpaused
 }
 
 impl Market {
+    // new markets start with the fees of the program config
+    pub fn init(
+        &mut self,
+        config: &ProgramConfig,
+        creator: Pubkey,
+        tick_size: u64,
+        base_lot_size: u64,
+        quote_lot_size: u64,
+        end_time: u64,
+    ) -> Result<()> {
+        if tick_size == 0 || base_lot_size == 0 || quote_lot_size == 0 {
+            return err!(EmberErr::InvalidLotSizes);
+        }
+        if end_time < Clock::get()?.unix_timestamp as u64 + config.min_market_duration {
+            return err!(EmberErr::DurationTooShort);
+        }
+        self.maker_fee_bps = config.maker_fee_bps;
+        self.taker_fee_bps = config.taker_fee_bps;
+        self.referral_share_bps = config.referral_share_bps;
+        self.creator = creator;
+        self.resolved = false;
+        self.status = MarketStatus::Open;
+        self.end_time = end_time;
+        self.tick_size = tick_size;
+        self.base_lot_size = base_lot_size;
+        self.quote_lot_size = quote_lot_size;
+        Ok(())
+    }
+
+    pub fn set_question(&mut self, question: String) -> Result<()> {
+        if question.len() > MAX_QUESTION_LENGTH {
+            return err!(EmberErr::QuestionTooLong);
+        }
+        self.question = question.into_bytes();
+        Ok(())
+    }
+
+    // a winning outcome lot pays out collateral_per_ticket per token, that's the price no order can reach
+    pub fn set_max_price(&mut self, collateral_per_ticket: u64) -> Result<()> {
+        let lot_payout = collateral_per_ticket
+            .checked_mul(self.base_lot_size)
+            .ok_or(EmberErr::AmountOverflow)?;
+        let price_unit = self.quote_lot_size * self.tick_size;
+        if lot_payout % price_unit != 0 {
+            return err!(EmberErr::InvalidLotSizes);
+        }
+        self.max_price = lot_payout / price_unit;
+        if self.max_price < 2 || self.max_price > MAX_ORDER_PRICE {
+            return err!(EmberErr::InvalidLotSizes);
+        }
+        Ok(())
+    }
+
     pub fn confirm_base_account(&self, orderbook: Pubkey, base_account: Pubkey) -> bool {
         if orderbook == self.orderbook_state_1 {
             if base_account == self.base_vault_1 {
@@ -39,7 +131,45 @@ impl Market {
         }
     }
 
-    pub const SIZE: usize = 200 + 32 + 8 + 8 + 32 + 32 + 32 + 32 + 1 + 32 + 32 + 32 + 32 + 32 + 32 + 32;
+    // balance token index of the outcome traded on the orderbook, 1 or 2
+    pub fn base_token(&self, orderbook: Pubkey) -> Result<u8> {
+        if orderbook == self.orderbook_state_1 {
+            Ok(1)
+        } else if orderbook == self.orderbook_state_2 {
+            Ok(2)
+        } else {
+            err!(EmberErr::InvalidMarket)
+        }
+    }
+
+    // orders are only taken while the market is open and before its end time, even if nobody closed it yet
+    pub fn is_open(&self, now: u64) -> bool {
+        self.status == MarketStatus::Open && !self.paused && now < self.end_time
+    }
+
+    pub fn require_open(&self) -> Result<()> {
+        if !self.is_open(Clock::get()?.unix_timestamp as u64) {
+            return err!(EmberErr::MarketClosed);
+        }
+        Ok(())
+    }
+
+    pub fn validate_price(&self, price: u64) -> Result<()> {
+        if price == 0 || price >= self.max_price {
+            return err!(EmberErr::InvalidPrice);
+        }
+        Ok(())
+    }
+
+    pub fn base_lots_to_native(&self, lots: u64) -> Result<u64> {
+        Ok(lots.checked_mul(self.base_lot_size).ok_or(EmberErr::AmountOverflow)?)
+    }
+
+    pub fn quote_lots_to_native(&self, lots: u64) -> Result<u64> {
+        Ok(lots.checked_mul(self.quote_lot_size).ok_or(EmberErr::AmountOverflow)?)
+    }
+
+    pub const SIZE: usize = MAX_QUESTION_LENGTH + 32 + 8 + 8 + 32 + 32 + 32 + 32 + 32 + 1 + 32 + 32 + 32 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 32 + 32 + 8 + 8 + 8 + 1 + 8 + 1 + 8 + 8 + 32 + 8 + 8 + 1;
 }
 
 #[account(zero_copy)]
@@ -85,6 +215,99 @@ impl UsersBalances {
 
         Ok(())
     }
+
+    // after resolution every winning base lot is worth max_price ticks of quote, losing ones nothing
+    pub fn settle_user(&mut self, uid: u64, winner: u8, max_price: u64, tick_size: u64) -> Result<()> {
+        let balance = &mut self.users[uid as usize];
+        let winning = if winner == 1 { balance.base_1 } else { balance.base_2 };
+        let payout = (winning as u128 * max_price as u128 * tick_size as u128)
+            .try_into()
+            .map_err(|_| error!(EmberErr::AmountOverflow))?;
+        balance.quote = balance.quote.checked_add(payout).ok_or(EmberErr::AmountOverflow)?;
+        balance.base_1 = 0;
+        balance.base_2 = 0;
+        Ok(())
+    }
+
+    // gives back the funds locked by a resting order, quote and the maker fee for bids and base for asks
+    pub fn refund_order(&mut self, order: &Order, side: Side, base_token: u8, tick_size: u64) -> Result<()> {
+        match side {
+            Side::Bid => {
+                let quote = quote_lots(order.size, order.price, tick_size)?;
+                self.credit_account(order.uid, quote + order.fee_locked, 0)
+            }
+            Side::Ask => self.credit_account(order.uid, order.size, base_token),
+        }
+    }
+
+    // maker side settlement of an event from the queue. a filled bid receives the base it bought, its maker
+    // fee was locked with the order, and a filled ask the quote less its maker fee. rebates and the part of
+    // a bid's fee lock the event released are paid in quote. an order taken out of the book gets its locked
+    // funds back
+    pub fn settle_event(&mut self, event: &Event, tick_size: u64) -> Result<()> {
+        let side = Side::from(event.side);
+        let base_token = event.base_token as u8;
+        let uid = event.maker_uid;
+        let quote = quote_lots(event.size, event.price, tick_size)?;
+        if event.event_type == EVENT_FILL {
+            match side {
+                Side::Bid => self.credit_account(uid, event.size, base_token)?,
+                Side::Ask => self.credit_account(uid, quote - event.maker_fee, 0)?,
+            }
+            self.credit_account(uid, event.maker_rebate + event.fee_unlocked, 0)
+        } else {
+            match side {
+                Side::Bid => self.credit_account(uid, quote + event.fee_unlocked, 0),
+                Side::Ask => self.credit_account(uid, event.size, base_token),
+            }
+        }
+    }
+}
+
+// quote lots paid for size base lots at a price in ticks
+pub fn quote_lots(size: u64, price: u64, tick_size: u64) -> Result<u64> {
+    Ok(size
+        .checked_mul(price)
+        .and_then(|lots| lots.checked_mul(tick_size))
+        .ok_or(EmberErr::AmountOverflow)?)
+}
+
+fn bps_of(quote: u64, fee_bps: u64, round_up: bool) -> u64 {
+    let mut fee = quote as u128 * fee_bps as u128;
+    if round_up {
+        fee += BPS_DENOMINATOR as u128 - 1;
+    }
+    (fee / BPS_DENOMINATOR as u128) as u64
+}
+
+// taker fees are rounded up
+pub fn taker_fee(quote: u64, fee_bps: u64) -> u64 {
+    bps_of(quote, fee_bps, true)
+}
+
+// maker fee and maker rebate on the quote of a fill, both rounded down. a bid's fee is taken from what it
+// locked, which was rounded down as well so the fills of an order never take more than its lock
+pub fn maker_fee(quote: u64, fee_bps: i64) -> (u64, u64) {
+    if fee_bps >= 0 {
+        (bps_of(quote, fee_bps as u64, false), 0)
+    } else {
+        (0, bps_of(quote, fee_bps.unsigned_abs(), false))
+    }
+}
+
+// referrer's share of a taker fee, rounded down
+pub fn referral_share(taker_fee: u64, share_bps: u64) -> u64 {
+    bps_of(taker_fee, share_bps, false)
+}
+
+// creator's share of the fees withdrawn, rounded down
+pub fn creator_share(fees: u64, share_bps: u64) -> u64 {
+    bps_of(fees, share_bps, false)
+}
+
+// extra quote a resting bid locks for its maker fee
+pub fn maker_fee_lock(quote: u64, fee_bps: i64) -> u64 {
+    maker_fee(quote, fee_bps).0
 }
 
 #[zero_copy]
@@ -92,8 +315,10 @@ pub struct Balance {
     pub quote: u64,
     pub base_1: u64,
     pub base_2: u64,
+    pub rewards: u64, // native reward tokens earned by quoting, paid by claim_rewards
 }
 
+// volumes are in native quote tokens, the same across markets
 #[account]
 pub struct User {
     pub owner: Pubkey,
@@ -102,6 +327,37 @@ pub struct User {
     pub volume: u64,
     pub winning_bets: u64,
     pub losing_bets: u64,
+    pub period: u64, // index of the VOLUME_PERIOD the period volume is counted in
+    pub period_volume: u64,
+    pub previous_period_volume: u64,
+    pub referrer: Pubkey, // user account of the referrer, default when the user wasn't referred
+    pub referrals: u64,   // users that signed up with this user as their referrer
+}
+
+impl User {
+    // volume is only a statistic and a fee tier input, it saturates rather than failing the trade
+    pub fn add_volume(&mut self, amount: u64, now: u64) {
+        let period = now / VOLUME_PERIOD;
+        if period != self.period {
+            self.previous_period_volume = if period == self.period + 1 { self.period_volume } else { 0 };
+            self.period_volume = 0;
+            self.period = period;
+        }
+        self.volume = self.volume.saturating_add(amount);
+        self.period_volume = self.period_volume.saturating_add(amount);
+    }
+
+    // volume of the current and the previous period, what the fee tiers go by
+    pub fn trailing_volume(&self, now: u64) -> u64 {
+        let period = now / VOLUME_PERIOD;
+        if period == self.period {
+            self.period_volume.saturating_add(self.previous_period_volume)
+        } else if period == self.period + 1 {
+            self.period_volume
+        } else {
+            0
+        }
+    }
 }
 
 #[account]
@@ -111,5 +367,123 @@ pub struct MarketSpecificUser {
     // add avg buy in, would be nice to calculate user's pnl on without fetching transactions
 }
 
+// referral fees a referrer earned on a market, they stay in the market's quote vault until claimed.
+// amounts are in native quote tokens
+#[account]
+pub struct ReferralFees {
+    pub fees: u64, // claimable
+    pub total_fees: u64,
+    pub referred_volume: u64,
+}
+
+// global table of fee discounts by trailing volume, sorted by min_volume
+#[account]
+pub struct FeeTiers {
+    pub tiers: Vec<FeeTier>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct FeeTier {
+    pub min_volume: u64,
+    pub maker_discount_bps: u64, // off positive maker fees, rebates aren't changed
+    pub taker_discount_bps: u64,
+}
+
+impl FeeTiers {
+    pub const SIZE: usize = 8 + 4 + MAX_FEE_TIERS * (8 + 8 + 8);
+
+    // maker and taker fees of a user with the given trailing volume on the market
+    pub fn user_fees(tiers: Option<&FeeTiers>, market: &Market, volume: u64) -> (i64, u64) {
+        let tier = tiers.and_then(|tiers| tiers.tiers.iter().rev().find(|tier| tier.min_volume <= volume));
+        let tier = match tier {
+            Some(tier) => tier,
+            None => return (market.maker_fee_bps, market.taker_fee_bps),
+        };
+        let discounted = |fee_bps: u64, discount_bps: u64| fee_bps * (BPS_DENOMINATOR - discount_bps) / BPS_DENOMINATOR;
+        let maker_fee_bps = if market.maker_fee_bps > 0 {
+            discounted(market.maker_fee_bps as u64, tier.maker_discount_bps) as i64
+        } else {
+            market.maker_fee_bps
+        };
+        (maker_fee_bps, discounted(market.taker_fee_bps, tier.taker_discount_bps))
+    }
+}
+
+// global config of the program, a pda initialized once by the upgrade authority
+#[account]
+pub struct ProgramConfig {
+    pub admins: Vec<Admin>,
+    // fees new markets start with
+    pub maker_fee_bps: i64,
+    pub taker_fee_bps: u64,
+    pub referral_share_bps: u64,
+    // bond posted by market creators without the market creator role, and limits of all new markets
+    pub creator_bond: u64,
+    pub max_creator_fee_bps: u64,
+    pub min_market_duration: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct Admin {
+    pub key: Pubkey,
+    pub roles: u64,
+}
+
+impl ProgramConfig {
+    pub const SIZE: usize = 8 + 4 + MAX_ADMINS * (32 + 8) + 8 + 8 + 8 + 8 + 8 + 8;
+
+    // the upgrade authority starts as the only admin, with every role
+    pub fn init(&mut self, upgrade_authority: Pubkey) {
+        self.admins = vec![Admin {
+            key: upgrade_authority,
+            roles: ALL_ROLES,
+        }];
+        self.maker_fee_bps = 0;
+        self.taker_fee_bps = 0;
+        self.referral_share_bps = 0;
+        self.creator_bond = CREATOR_BOND;
+        self.max_creator_fee_bps = MAX_CREATOR_FEE_BPS;
+        self.min_market_duration = MIN_MARKET_DURATION;
+    }
+
+    pub fn has_role(&self, key: &Pubkey, role: u64) -> bool {
+        self.admins.iter().any(|admin| admin.key == *key && admin.roles & role == role)
+    }
+
+    // adds the admin or replaces its roles
+    pub fn set_admin(&mut self, key: Pubkey, roles: u64) -> Result<()> {
+        if roles == 0 || roles & !ALL_ROLES != 0 {
+            return err!(EmberErr::InvalidRoles);
+        }
+        match self.admins.iter_mut().find(|admin| admin.key == key) {
+            Some(admin) => admin.roles = roles,
+            None => {
+                if self.admins.len() >= MAX_ADMINS {
+                    return err!(EmberErr::TooManyAdmins);
+                }
+                self.admins.push(Admin { key, roles });
+            }
+        }
+        self.require_admin_role()
+    }
+
+    pub fn remove_admin(&mut self, key: &Pubkey) -> Result<()> {
+        let len = self.admins.len();
+        self.admins.retain(|admin| admin.key != *key);
+        if self.admins.len() == len {
+            return err!(EmberErr::AdminNotFound);
+        }
+        self.require_admin_role()
+    }
+
+    // the config can't be left without anyone able to manage it
+    fn require_admin_role(&self) -> Result<()> {
+        if !self.admins.iter().any(|admin| admin.roles & ROLE_ADMIN != 0) {
+            return err!(EmberErr::LastAdmin);
+        }
+        Ok(())
+    }
+}
+
 #[account]
 pub struct Auth {}
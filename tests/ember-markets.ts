@@ -2,13 +2,18 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { EmberMarkets } from "../target/types/ember_markets";
 import { BinaryOutcomeTokens } from "../target/types/binary_outcome_tokens";
-import { TOKEN_PROGRAM_ID, createAccount, createMint, mintTo } from "@solana/spl-token";
+import { TOKEN_PROGRAM_ID, createAccount, createMint, getAccount, mintTo } from "@solana/spl-token";
+import { keccak_256 } from "@noble/hashes/sha3";
+import { expect } from "chai";
+import * as fs from "fs";
 
 
 
 const USER_ACCOUNT_PDA_SEED = Buffer.from("user_account_pda_seed");
 const CONDITION_AUTH_PDA_SEED = Buffer.from("condition_auth_pda_seed");
 const MARKET_AUTH_SEED = Buffer.from("market_auth_seed");
+const WALLET_POSITION_SEED = Buffer.from("wallet_position_seed");
+const PROGRAM_CONFIG_SEED = Buffer.from("program_config");
 
 let ticketTokenMint: anchor.web3.PublicKey;
 let yesToken: anchor.web3.PublicKey;
@@ -37,6 +42,149 @@ describe("binary-outcome-tokens", () => {
 
   const EmberProgram = anchor.workspace.EmberMarkets as Program<EmberMarkets>;
   const BOTProgram = anchor.workspace.BinaryOutcomeTokens as Program<BinaryOutcomeTokens>;
+  const connection = BOTProgram.provider.connection;
+  const wallet = BOTProgram.provider.publicKey;
+
+  // fails unless the transaction is rejected with the given error code
+  async function expectError(tx: Promise<unknown>, code: string) {
+    try {
+      await tx;
+    } catch (e) {
+      expect(String(e) + (e.logs ?? []).join("\n")).to.include(code);
+      return;
+    }
+    expect.fail(`expected ${code}`);
+  }
+
+  type TestCondition = {
+    condition: anchor.web3.PublicKey;
+    conditionAuthPda: anchor.web3.PublicKey;
+    ticketTokenMint: anchor.web3.PublicKey;
+    yesToken: anchor.web3.PublicKey;
+    noToken: anchor.web3.PublicKey;
+    collateralVault: anchor.web3.PublicKey;
+  };
+
+  // a condition resolved by the provider wallet, 100 collateral per ticket
+  async function createCondition(freezable = false): Promise<TestCondition> {
+    const conditionKeypair = new anchor.web3.Keypair();
+    const vaultKeypair = new anchor.web3.Keypair();
+    const [conditionAuthPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [CONDITION_AUTH_PDA_SEED, conditionKeypair.publicKey.toBuffer()],
+      BOTProgram.programId
+    );
+    const freezeAuthority = freezable ? conditionAuthPda : null;
+    const ticketTokenMint = await createMint(connection, payer, conditionAuthPda, null, 0);
+    const yesToken = await createMint(connection, payer, conditionAuthPda, freezeAuthority, 0);
+    const noToken = await createMint(connection, payer, conditionAuthPda, freezeAuthority, 0);
+
+    await BOTProgram.methods.initializeCondition("test",
+      "a random token description",
+      "yes",
+      "no",
+      new anchor.BN(100)).accounts({
+        signer: wallet,
+        condition: conditionKeypair.publicKey,
+        conditionAuthPda,
+        ticketTokenMint,
+        outcomeToken1: yesToken,
+        outcomeToken2: noToken,
+        collateralToken,
+        collateralVault: vaultKeypair.publicKey,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        resolutionAuth: null,
+      })
+      .signers([conditionKeypair, vaultKeypair])
+      .rpc(OPTS);
+
+    return {
+      condition: conditionKeypair.publicKey,
+      conditionAuthPda,
+      ticketTokenMint,
+      yesToken,
+      noToken,
+      collateralVault: vaultKeypair.publicKey,
+    };
+  }
+
+  type Wallet = { kp: anchor.web3.Keypair; quote: anchor.web3.PublicKey };
+
+  // a funded wallet holding 1000 usdc
+  async function createWallet(kp = new anchor.web3.Keypair()): Promise<Wallet> {
+    await connection.confirmTransaction(await connection.requestAirdrop(kp.publicKey, 10_000_000_000));
+    const quote = await createAccount(connection, payer, collateralToken, kp.publicKey);
+    await mintTo(connection, payer, collateralToken, quote, payer, 1_000_000_000);
+    return { kp, quote };
+  }
+
+  type Positions = {
+    ticket: anchor.web3.PublicKey;
+    yes: anchor.web3.PublicKey;
+    no: anchor.web3.PublicKey;
+  };
+
+  async function createPositions(cond: TestCondition, owner: anchor.web3.PublicKey): Promise<Positions> {
+    return {
+      ticket: await createAccount(connection, payer, cond.ticketTokenMint, owner),
+      yes: await createAccount(connection, payer, cond.yesToken, owner),
+      no: await createAccount(connection, payer, cond.noToken, owner),
+    };
+  }
+
+  function walletPositionPda(cond: TestCondition, owner: anchor.web3.PublicKey) {
+    return anchor.web3.PublicKey.findProgramAddressSync(
+      [WALLET_POSITION_SEED, cond.condition.toBuffer(), owner.toBuffer()],
+      BOTProgram.programId
+    )[0];
+  }
+
+  function mintTickets(cond: TestCondition, w: Wallet, p: Positions, amount: anchor.BN | number, walletPosition: anchor.web3.PublicKey | null = null) {
+    return BOTProgram.methods.mintTicket(new anchor.BN(amount)).accounts({
+      signer: w.kp.publicKey,
+      condition: cond.condition,
+      collateralVault: cond.collateralVault,
+      conditionAuthPda: cond.conditionAuthPda,
+      ticketTokenMint: cond.ticketTokenMint,
+      payer: w.quote,
+      receiver: p.ticket,
+      walletPosition,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([w.kp]);
+  }
+
+  function splitTickets(cond: TestCondition, w: Wallet, p: Positions, amount: number, walletPosition: anchor.web3.PublicKey | null = null) {
+    return BOTProgram.methods.splitTicket(new anchor.BN(amount)).accounts({
+      signer: w.kp.publicKey,
+      condition: cond.condition,
+      conditionAuthPda: cond.conditionAuthPda,
+      ticketTokenMint: cond.ticketTokenMint,
+      outcome1Token: cond.yesToken,
+      outcome2Token: cond.noToken,
+      payer: p.ticket,
+      receiver1: p.yes,
+      receiver2: p.no,
+      walletPosition,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([w.kp]);
+  }
+
+  // mints amount tickets and splits them into outcome tokens
+  async function mintSets(cond: TestCondition, w: Wallet, p: Positions, amount: number) {
+    await mintTickets(cond, w, p, amount).rpc();
+    await splitTickets(cond, w, p, amount).rpc();
+  }
+
+  async function tokenAmount(account: anchor.web3.PublicKey) {
+    return Number((await getAccount(connection, account)).amount);
+  }
+
+  async function logsOf(signature: string) {
+    const tx = await connection.getTransaction(signature, { commitment: "confirmed" });
+    return tx.meta.logMessages.join("\n");
+  }
+
 
   it("Initializing condition", async () => {
 
@@ -124,6 +272,7 @@ describe("binary-outcome-tokens", () => {
         rent: anchor.web3.SYSVAR_RENT_PUBKEY,
         systemProgram: anchor.web3.SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
+        resolutionAuth: null,
       })
       .signers([conditionKeypair, vaultKeypair])
       .
@@ -148,6 +297,7 @@ describe("binary-outcome-tokens", () => {
       ticketTokenMint,
       payer: collateralTokenAta,
       receiver: ticketTokenAta,
+      walletPosition: null,
       tokenProgram: TOKEN_PROGRAM_ID,
     }).rpc(OPTS);
   });
@@ -179,10 +329,25 @@ describe("binary-outcome-tokens", () => {
       payer: ticketTokenAta,
       receiver1: yesTokenAta,
       receiver2: noTokenAta,
+      walletPosition: null,
       tokenProgram: TOKEN_PROGRAM_ID,
     }).rpc(OPTS);
   });
 
+  const [programConfig] = anchor.web3.PublicKey.findProgramAddressSync([PROGRAM_CONFIG_SEED], EmberProgram.programId);
+
+  it("Initialize program config", async () => {
+    const [programData] = anchor.web3.PublicKey.findProgramAddressSync(
+      [EmberProgram.programId.toBuffer()], anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID);
+    await EmberProgram.methods.initializeProgramConfig().accounts({
+      signer: EmberProgram.provider.publicKey,
+      config: programConfig,
+      program: EmberProgram.programId,
+      programData,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    }).rpc(OPTS);
+  });
+
   it("Initialize Market", async () => {
     let market_ = new anchor.web3.Keypair();
     market = market_.publicKey;
@@ -192,40 +357,50 @@ describe("binary-outcome-tokens", () => {
     const orderbook2Ix = await EmberProgram.account.orderBookState.createInstruction(orderbook_2)
     let balances = new anchor.web3.Keypair();
     const balancesIx = await EmberProgram.account.usersBalances.createInstruction(balances)
+    let eventQueue = new anchor.web3.Keypair();
+    const eventQueueIx = await EmberProgram.account.eventQueue.createInstruction(eventQueue)
 
     const [marketAuthPda, _] = anchor.web3.PublicKey.findProgramAddressSync([MARKET_AUTH_SEED, market.toBuffer()], EmberProgram.programId);
 
     const baseVault1 = new anchor.web3.Keypair();
     const baseVault2 = new anchor.web3.Keypair();
     const quoteVault = new anchor.web3.Keypair();
+    const ticketVault = new anchor.web3.Keypair();
+    const feeVault = new anchor.web3.Keypair();
 
-    await EmberProgram.methods.initializeMarket().accounts({
+    await EmberProgram.methods.initializeMarket(new anchor.BN(1), new anchor.BN(1), new anchor.BN(1), new anchor.BN(Math.floor(Date.now() / 1000) + 7 * 24 * 60 * 60)).accounts({
       signer: EmberProgram.provider.publicKey,
+      config: programConfig,
       market: market,
       orderbookState1: orderbook_1.publicKey,
       orderbookState2: orderbook_2.publicKey,
       balances: balances.publicKey,
+      eventQueue: eventQueue.publicKey,
       systemProgram: anchor.web3.SystemProgram.programId,
       tokenProgram: TOKEN_PROGRAM_ID,
-    }).signers([orderbook_1, orderbook_2, balances, market_])
-      .preInstructions([orderbook1Ix, orderbook2Ix, balancesIx])
+    }).signers([orderbook_1, orderbook_2, balances, eventQueue, market_])
+      .preInstructions([orderbook1Ix, orderbook2Ix, balancesIx, eventQueueIx])
       .rpc();
 
     const tx = await EmberProgram.methods.initializeVaults().accounts({
       baseToken1: yesToken,
       baseToken2: noToken,
       quoteToken: collateralToken,
+      ticketToken: ticketTokenMint,
       baseVault1: baseVault1.publicKey,
       baseVault2: baseVault2.publicKey,
       quoteVault: quoteVault.publicKey,
+      ticketVault: ticketVault.publicKey,
+      feeVault: feeVault.publicKey,
       market: market,
       signer: EmberProgram.provider.publicKey,
+      config: programConfig,
       tokenProgram: TOKEN_PROGRAM_ID,
       condition,
       marketAuthPda,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
       systemProgram: anchor.web3.SystemProgram.programId,
-    }).signers([baseVault1, baseVault2, quoteVault])
+    }).signers([baseVault1, baseVault2, quoteVault, ticketVault, feeVault])
       .rpc(OPTS);
   });
 
@@ -234,12 +409,13 @@ describe("binary-outcome-tokens", () => {
 
 
   it("user account", async () => {
-    EmberProgram.methods.createUserAccount().accounts({
+    await EmberProgram.methods.createUserAccount().accounts({
       signer: EmberProgram.provider.publicKey,
       market: market,
       rent: anchor.web3.SYSVAR_RENT_PUBKEY,
       systemProgram: anchor.web3.SystemProgram.programId,
-      userAccount: userAccountPda
+      userAccount: userAccountPda,
+      referrer: null,
     }).rpc(OPTS);
   });
 
@@ -280,6 +456,7 @@ describe("binary-outcome-tokens", () => {
       signer: BOTProgram.provider.publicKey,
       condition,
       conditionAuthPda,
+      group: null,
     }).rpc(OPTS);
   });
 
@@ -292,8 +469,2068 @@ describe("binary-outcome-tokens", () => {
       payer: yesTokenAta,
       collateralVault,
       receiver: collateralTokenAta,
+      group: null,
       tokenProgram: TOKEN_PROGRAM_ID,
     }).rpc(OPTS);
   });
+
+  function configureMintGate(cond: TestCondition, gateMode: number, allowlistRoot: Buffer, walletCap: number, globalCap: number, attestationProgram = anchor.web3.PublicKey.default) {
+    return BOTProgram.methods.configureMintGate(
+      new anchor.BN(gateMode),
+      Array.from(allowlistRoot),
+      attestationProgram,
+      new anchor.BN(walletCap),
+      new anchor.BN(globalCap),
+    ).accounts({
+      signer: wallet,
+      condition: cond.condition,
+    });
+  }
+
+  function registerWallet(cond: TestCondition, w: Wallet, proof: Buffer[] = [], attestation: anchor.web3.PublicKey | null = null) {
+    return BOTProgram.methods.registerWallet(proof.map((node) => Array.from(node))).accounts({
+      signer: w.kp.publicKey,
+      condition: cond.condition,
+      walletPosition: walletPositionPda(cond, w.kp.publicKey),
+      attestation,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    }).signers([w.kp]);
+  }
+
+  describe("mint gates", () => {
+    let cond: TestCondition;
+    let w: Wallet;
+    let p: Positions;
+    let walletPosition: anchor.web3.PublicKey;
+
+    // open to any registered wallet, up to 3 tickets of collateral each and 10 in total
+    before(async () => {
+      cond = await createCondition();
+      w = await createWallet();
+      p = await createPositions(cond, w.kp.publicKey);
+      walletPosition = walletPositionPda(cond, w.kp.publicKey);
+      await configureMintGate(cond, 0, Buffer.alloc(32), 300, 1_000).rpc();
+      await registerWallet(cond, w).rpc();
+    });
+
+    it("Minting gated tickets within the wallet cap", async () => {
+      await mintTickets(cond, w, p, 2, walletPosition).rpc();
+      await splitTickets(cond, w, p, 2, walletPosition).rpc();
+
+      const position = await BOTProgram.account.walletPosition.fetch(walletPosition);
+      expect(position.collateral.toNumber()).to.equal(200);
+      expect(position.unsplitTickets.toNumber()).to.equal(0);
+      expect(await tokenAmount(p.yes)).to.equal(2);
+    });
+
+    it("Rejecting gated mints over the caps or without a registered wallet", async () => {
+      await expectError(mintTickets(cond, w, p, 2, walletPosition).rpc(), "WalletCapExceeded");
+
+      const other = await createWallet();
+      const otherPositions = await createPositions(cond, other.kp.publicKey);
+      await expectError(mintTickets(cond, other, otherPositions, 1).rpc(), "WalletPositionRequired");
+
+      await registerWallet(cond, other).rpc();
+      const otherPosition = walletPositionPda(cond, other.kp.publicKey);
+      await expectError(mintTickets(cond, other, otherPositions, 9, otherPosition).rpc(), "GlobalCapExceeded");
+    });
+
+    it("Rejecting mints whose collateral overflows", async () => {
+      await expectError(mintTickets(cond, w, p, new anchor.BN("18446744073709551615"), walletPosition).rpc(), "AmountOverflow");
+    });
+  });
+
+  // leaves are the keccak hash of the wallet, pairs are hashed sorted like register_wallet does
+  function hashPair(a: Buffer, b: Buffer) {
+    return Buffer.from(keccak_256(Buffer.compare(a, b) <= 0 ? Buffer.concat([a, b]) : Buffer.concat([b, a])));
+  }
+
+  function allowlistLeaf(w: Wallet) {
+    return Buffer.from(keccak_256(w.kp.publicKey.toBuffer()));
+  }
+
+  // tests/fixtures/attestation.json is loaded by the validator, it's the attestation of this wallet issued by ATTESTATION_PROGRAM
+  const ATTESTATION_PROGRAM = new anchor.web3.PublicKey("868nYLkjj1HGrVHouG5EXettKvG9ggFKY4SUCzmiZTtE");
+  const attestedKeypair = anchor.web3.Keypair.fromSecretKey(
+    Uint8Array.from(JSON.parse(fs.readFileSync("tests/fixtures/attested-wallet.json", "utf8"))));
+
+  describe("allowlists and attestations", () => {
+    let allowlisted: TestCondition;
+    let attested: TestCondition;
+    let listed: Wallet[];
+    let attestedWallet: Wallet;
+    let proof: Buffer[];
+
+    before(async () => {
+      listed = [await createWallet(), await createWallet(), await createWallet()];
+      const [a, b, c] = listed.map(allowlistLeaf);
+      // the first wallet proves its leaf with its sibling and the hash of the other half of the tree
+      proof = [b, c];
+      allowlisted = await createCondition();
+      await configureMintGate(allowlisted, 1, hashPair(hashPair(a, b), c), 300, 0).rpc();
+
+      attestedWallet = await createWallet(attestedKeypair);
+      attested = await createCondition();
+      await configureMintGate(attested, 2, Buffer.alloc(32), 0, 0, ATTESTATION_PROGRAM).rpc();
+    });
+
+    it("Registering an allowlisted wallet with its merkle proof and minting under the wallet cap", async () => {
+      const w = listed[0];
+      const p = await createPositions(allowlisted, w.kp.publicKey);
+      const walletPosition = walletPositionPda(allowlisted, w.kp.publicKey);
+      await registerWallet(allowlisted, w, proof).rpc();
+      await mintTickets(allowlisted, w, p, 2, walletPosition).rpc();
+
+      const position = await BOTProgram.account.walletPosition.fetch(walletPosition);
+      expect(position.wallet.toBase58()).to.equal(w.kp.publicKey.toBase58());
+      expect(position.collateral.toNumber()).to.equal(200);
+      expect(await tokenAmount(p.ticket)).to.equal(2);
+    });
+
+    it("Registering a wallet holding an attestation", async () => {
+      const [attestation] = anchor.web3.PublicKey.findProgramAddressSync([attestedKeypair.publicKey.toBuffer()], ATTESTATION_PROGRAM);
+      const p = await createPositions(attested, attestedKeypair.publicKey);
+      const walletPosition = walletPositionPda(attested, attestedKeypair.publicKey);
+      await registerWallet(attested, attestedWallet, [], attestation).rpc();
+      await mintTickets(attested, attestedWallet, p, 1, walletPosition).rpc();
+
+      expect(await tokenAmount(p.ticket)).to.equal(1);
+    });
+
+    it("Rejecting wallets missing from the allowlist or without an attestation", async () => {
+      // a listed wallet's proof doesn't work for another wallet
+      const outsider = await createWallet();
+      await expectError(registerWallet(allowlisted, outsider, proof).rpc(), "WalletNotAllowed");
+      await expectError(registerWallet(allowlisted, listed[1]).rpc(), "WalletNotAllowed");
+
+      // an attestation is only valid for the wallet it was derived from
+      const [attestation] = anchor.web3.PublicKey.findProgramAddressSync([attestedKeypair.publicKey.toBuffer()], ATTESTATION_PROGRAM);
+      await expectError(registerWallet(attested, outsider, [], attestation).rpc(), "WalletNotAllowed");
+      await expectError(registerWallet(attested, outsider).rpc(), "WalletNotAllowed");
+
+      await expectError(configureMintGate(attested, 3, Buffer.alloc(32), 0, 0).rpc(), "InvalidGateMode");
+    });
+  });
+
+  function resolveCondition(cond: TestCondition, outcome: number, group: anchor.web3.PublicKey | null = null) {
+    return BOTProgram.methods.resolveCondition(new anchor.BN(outcome)).accounts({
+      signer: wallet,
+      condition: cond.condition,
+      conditionAuthPda: cond.conditionAuthPda,
+      group,
+    });
+  }
+
+  function setDisputeStatus(cond: TestCondition, disputed: number) {
+    return BOTProgram.methods.setDisputeStatus(new anchor.BN(disputed)).accounts({
+      signer: wallet,
+      condition: cond.condition,
+    });
+  }
+
+  function redeemPayout(cond: TestCondition, w: Wallet, outcomeToken: anchor.web3.PublicKey, account: anchor.web3.PublicKey, amount: number, group: anchor.web3.PublicKey | null = null) {
+    return BOTProgram.methods.redeemPayout(new anchor.BN(amount)).accounts({
+      signer: w.kp.publicKey,
+      condition: cond.condition,
+      conditionAuthPda: cond.conditionAuthPda,
+      outcomeToken,
+      payer: account,
+      collateralVault: cond.collateralVault,
+      receiver: w.quote,
+      group,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([w.kp]);
+  }
+
+  function outcomeAccounts(accounts: anchor.web3.PublicKey[]) {
+    return accounts.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false }));
+  }
+
+  function freezeOutcomeAccounts(cond: TestCondition, accounts: anchor.web3.PublicKey[]) {
+    return BOTProgram.methods.freezeOutcomeAccounts().accounts({
+      signer: wallet,
+      condition: cond.condition,
+      conditionAuthPda: cond.conditionAuthPda,
+      outcome1Token: cond.yesToken,
+      outcome2Token: cond.noToken,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).remainingAccounts(outcomeAccounts(accounts));
+  }
+
+  function thawOutcomeAccounts(cond: TestCondition, signer: Wallet, accounts: anchor.web3.PublicKey[]) {
+    return BOTProgram.methods.thawOutcomeAccounts().accounts({
+      signer: signer.kp.publicKey,
+      condition: cond.condition,
+      conditionAuthPda: cond.conditionAuthPda,
+      outcome1Token: cond.yesToken,
+      outcome2Token: cond.noToken,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).remainingAccounts(outcomeAccounts(accounts)).signers([signer.kp]);
+  }
+
+  describe("dispute freezes", () => {
+    let cond: TestCondition;
+    let w: Wallet;
+    let p: Positions;
+
+    // a freezable condition resolved to its first outcome and then disputed, the wallet holds 2 sets
+    before(async () => {
+      cond = await createCondition(true);
+      w = await createWallet();
+      p = await createPositions(cond, w.kp.publicKey);
+      await mintSets(cond, w, p, 2);
+      await resolveCondition(cond, 0).rpc();
+      await setDisputeStatus(cond, 1).rpc();
+    });
+
+    it("Freezing outcome accounts while a resolution is disputed", async () => {
+      await freezeOutcomeAccounts(cond, [p.yes]).rpc();
+      // accounts already frozen are skipped and not counted
+      const signature = await freezeOutcomeAccounts(cond, [p.yes, p.no]).rpc({ commitment: "confirmed" });
+
+      expect(await logsOf(signature)).to.include("froze 1 outcome token accounts");
+      expect((await getAccount(connection, p.yes)).isFrozen).to.be.true;
+      expect((await getAccount(connection, p.no)).isFrozen).to.be.true;
+    });
+
+    it("Rejecting payouts, holder thaws and program owned accounts while the resolution is disputed", async () => {
+      await expectError(redeemPayout(cond, w, cond.yesToken, p.yes, 1).rpc(), "ConditionDisputed");
+      await expectError(thawOutcomeAccounts(cond, w, [p.yes]).rpc(), "ConditionDisputed");
+
+      // like the base vaults of a market, owned by its market auth pda
+      const [vaultAuth] = anchor.web3.PublicKey.findProgramAddressSync(
+        [MARKET_AUTH_SEED, new anchor.web3.Keypair().publicKey.toBuffer()], EmberProgram.programId);
+      const vault = await createAccount(connection, payer, cond.yesToken, vaultAuth, new anchor.web3.Keypair());
+      await expectError(freezeOutcomeAccounts(cond, [vault]).rpc(), "ProgramOwnedAccount");
+    });
+
+    it("Thawing outcome accounts and redeeming once the dispute is cleared", async () => {
+      await setDisputeStatus(cond, 0).rpc();
+      await thawOutcomeAccounts(cond, w, [p.yes, p.no]).rpc();
+      expect((await getAccount(connection, p.yes)).isFrozen).to.be.false;
+
+      const before = await tokenAmount(w.quote);
+      await redeemPayout(cond, w, cond.yesToken, p.yes, 2).rpc();
+      expect(await tokenAmount(w.quote)).to.equal(before + 200);
+    });
+
+    it("Rejecting freezes of undisputed or unfreezable conditions", async () => {
+      await expectError(freezeOutcomeAccounts(cond, [p.no]).rpc(), "ConditionNotDisputed");
+
+      const unfreezable = await createCondition();
+      await resolveCondition(unfreezable, 0).rpc();
+      await setDisputeStatus(unfreezable, 1).rpc();
+      await expectError(freezeOutcomeAccounts(unfreezable, []).rpc(), "ConditionNotFreezable");
+      await expectError(setDisputeStatus(unfreezable, 2).rpc(), "InvalidDisputeStatus");
+    });
+  });
+
+  function addGroupCondition(group: anchor.web3.PublicKey, cond: TestCondition) {
+    return BOTProgram.methods.addGroupCondition().accounts({
+      signer: wallet,
+      group,
+      condition: cond.condition,
+    });
+  }
+
+  describe("condition groups", () => {
+    const groupKeypair = new anchor.web3.Keypair();
+    const group = groupKeypair.publicKey;
+    const members: TestCondition[] = [];
+    const positions: Positions[] = [];
+    let w: Wallet;
+
+    function convertNoPositions(keepIndex: number, amount: number) {
+      const accounts: anchor.web3.AccountMeta[] = [];
+      members.forEach((member, i) => {
+        const kept = i == keepIndex;
+        accounts.push(
+          { pubkey: member.condition, isWritable: true, isSigner: false },
+          { pubkey: member.conditionAuthPda, isWritable: false, isSigner: false },
+          { pubkey: member.collateralVault, isWritable: true, isSigner: false },
+          { pubkey: kept ? member.yesToken : member.noToken, isWritable: true, isSigner: false },
+          { pubkey: kept ? positions[i].yes : positions[i].no, isWritable: true, isSigner: false },
+        );
+      });
+      return BOTProgram.methods.convertNoPositions(new anchor.BN(amount), new anchor.BN(keepIndex)).accounts({
+        signer: w.kp.publicKey,
+        group,
+        receiver: w.quote,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).remainingAccounts(accounts).signers([w.kp]);
+    }
+
+    // a group of 3 conditions, the wallet holds 2 sets of each
+    before(async () => {
+      await BOTProgram.methods.initializeConditionGroup(new anchor.BN(100)).accounts({
+        signer: wallet,
+        group,
+        collateralToken,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([groupKeypair]).rpc();
+
+      w = await createWallet();
+      for (let i = 0; i < 3; i++) {
+        const member = await createCondition();
+        await addGroupCondition(group, member).rpc();
+        const p = await createPositions(member, w.kp.publicKey);
+        await mintSets(member, w, p, 2);
+        members.push(member);
+        positions.push(p);
+      }
+    });
+
+    it("Converting NO positions of a condition group", async () => {
+      // NO on every member but the first is YES on the first, and one set of collateral is freed
+      const before = await tokenAmount(w.quote);
+      await convertNoPositions(0, 1).rpc();
+      expect(await tokenAmount(positions[0].yes)).to.equal(3);
+      expect(await tokenAmount(positions[1].no)).to.equal(1);
+      expect(await tokenAmount(positions[2].no)).to.equal(1);
+      expect(await tokenAmount(w.quote)).to.equal(before + 100);
+    });
+
+    it("Rejecting conversions keeping a gated member and conditions grouped twice", async () => {
+      await configureMintGate(members[2], 0, Buffer.alloc(32), 1_000_000, 0).rpc();
+      await expectError(convertNoPositions(2, 1).rpc(), "ConversionGated");
+      expect(await tokenAmount(positions[1].no)).to.equal(1);
+
+      await expectError(addGroupCondition(group, members[0]).rpc(), "ConditionAlreadyGrouped");
+    });
+
+    it("Rejecting a second YES in the group and re-resolving undisputed members", async () => {
+      await resolveCondition(members[0], 0, group).rpc();
+      await expectError(resolveCondition(members[1], 0, group).rpc(), "GroupWinnerAlreadySet");
+      await expectError(resolveCondition(members[0], 1, group).rpc(), "ConditionNotDisputed");
+    });
+
+    it("Re-resolving a disputed member and settling the group", async () => {
+      await setDisputeStatus(members[0], 1).rpc();
+      await resolveCondition(members[0], 1, group).rpc();
+      await setDisputeStatus(members[0], 0).rpc();
+      await resolveCondition(members[1], 0, group).rpc();
+      await resolveCondition(members[2], 1, group).rpc();
+      await expectError(
+        redeemPayout(members[1], w, members[1].yesToken, positions[1].yes, 2, group).rpc(),
+        "GroupNotSettled"
+      );
+
+      // the vault the conversion released collateral from is refilled from the others
+      const winners = [members[0].noToken, members[1].yesToken, members[2].noToken];
+      const accounts: anchor.web3.AccountMeta[] = [];
+      members.forEach((member, i) => {
+        accounts.push(
+          { pubkey: member.condition, isWritable: false, isSigner: false },
+          { pubkey: member.conditionAuthPda, isWritable: false, isSigner: false },
+          { pubkey: member.collateralVault, isWritable: true, isSigner: false },
+          { pubkey: winners[i], isWritable: false, isSigner: false },
+        );
+      });
+      await BOTProgram.methods.settleGroup().accounts({
+        group,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).remainingAccounts(accounts).rpc();
+
+      const before = await tokenAmount(w.quote);
+      await redeemPayout(members[1], w, members[1].yesToken, positions[1].yes, 2, group).rpc();
+      expect(await tokenAmount(w.quote)).to.equal(before + 200);
+    });
+  });
+
+  function flashMint(cond: TestCondition, w: Wallet, p: Positions, amount: number) {
+    return BOTProgram.methods.flashMint(new anchor.BN(amount)).accounts({
+      signer: w.kp.publicKey,
+      condition: cond.condition,
+      conditionAuthPda: cond.conditionAuthPda,
+      receiver1: p.yes,
+      receiver2: p.no,
+      outcome1Token: cond.yesToken,
+      outcome2Token: cond.noToken,
+      instructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([w.kp]);
+  }
+
+  function flashRepay(cond: TestCondition, w: Wallet, p: Positions, setsToBurn: number) {
+    return BOTProgram.methods.flashRepay(new anchor.BN(setsToBurn)).accounts({
+      signer: w.kp.publicKey,
+      condition: cond.condition,
+      payer1: p.yes,
+      payer2: p.no,
+      outcome1Token: cond.yesToken,
+      outcome2Token: cond.noToken,
+      collateralPayer: w.quote,
+      collateralVault: cond.collateralVault,
+      // fees go to the resolution authority of the condition
+      feeReceiver: collateralTokenAta,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([w.kp]);
+  }
+
+  async function flashMintAndRepay(cond: TestCondition, w: Wallet, p: Positions, amount: number, setsToBurn: number) {
+    const tx = new anchor.web3.Transaction().add(
+      await flashMint(cond, w, p, amount).instruction(),
+      await flashRepay(cond, w, p, setsToBurn).instruction(),
+    );
+    await (anchor.getProvider() as anchor.AnchorProvider).sendAndConfirm(tx, [w.kp]);
+  }
+
+  function setFlashFee(cond: TestCondition, feeBps: number) {
+    return BOTProgram.methods.setFlashFee(new anchor.BN(feeBps)).accounts({
+      signer: wallet,
+      condition: cond.condition,
+    });
+  }
+
+  describe("flash mints", () => {
+    let cond: TestCondition;
+    let w: Wallet;
+    let p: Positions;
+
+    before(async () => {
+      cond = await createCondition();
+      w = await createWallet();
+      p = await createPositions(cond, w.kp.publicKey);
+    });
+
+    it("Flash minting sets repaid in the same transaction", async () => {
+      // 2 of the 5 sets are kept and paid for in collateral
+      const before = await tokenAmount(w.quote);
+      await flashMintAndRepay(cond, w, p, 5, 3);
+      expect(await tokenAmount(p.yes)).to.equal(2);
+      expect(await tokenAmount(p.no)).to.equal(2);
+      expect(await tokenAmount(w.quote)).to.equal(before - 200);
+      expect(await tokenAmount(cond.collateralVault)).to.equal(200);
+
+      const condition = await BOTProgram.account.condition.fetch(cond.condition);
+      expect(condition.flashOutstanding.toNumber()).to.equal(0);
+    });
+
+    it("Charging the flash fee on the flash minted sets", async () => {
+      await setFlashFee(cond, 100).rpc();
+
+      // 1% of the 10 sets of collateral
+      const before = await tokenAmount(collateralTokenAta);
+      await flashMintAndRepay(cond, w, p, 10, 10);
+      expect(await tokenAmount(collateralTokenAta)).to.equal(before + 10);
+      expect(await tokenAmount(p.yes)).to.equal(2);
+    });
+
+    it("Rejecting flash mints without a repay, on gated conditions and flash fees over 100%", async () => {
+      await expectError(flashMint(cond, w, p, 5).rpc(), "FlashRepayMissing");
+      await expectError(flashRepay(cond, w, p, 0).rpc(), "NoFlashMintOutstanding");
+      await expectError(setFlashFee(cond, 10_001).rpc(), "InvalidFee");
+
+      const gated = await createCondition();
+      const gatedPositions = await createPositions(gated, w.kp.publicKey);
+      await configureMintGate(gated, 0, Buffer.alloc(32), 1_000_000, 0).rpc();
+      await expectError(flashMintAndRepay(gated, w, gatedPositions, 1, 1), "FlashMintGated");
+    });
+  });
+
+  function conditionStats(cond: TestCondition) {
+    return BOTProgram.methods.getConditionStats().accounts({ condition: cond.condition }).view();
+  }
+
+  describe("condition stats", () => {
+    let cond: TestCondition;
+    let w: Wallet;
+    let p: Positions;
+
+    before(async () => {
+      cond = await createCondition();
+      w = await createWallet();
+      p = await createPositions(cond, w.kp.publicKey);
+    });
+
+    it("Counting minted, split, merged and redeemed tickets", async () => {
+      await mintTickets(cond, w, p, 5).rpc();
+      await splitTickets(cond, w, p, 3).rpc();
+      await BOTProgram.methods.mergeTicket(new anchor.BN(1)).accounts({
+        signer: w.kp.publicKey,
+        condition: cond.condition,
+        conditionAuthPda: cond.conditionAuthPda,
+        ticketTokenMint: cond.ticketTokenMint,
+        outcome1Token: cond.yesToken,
+        outcome2Token: cond.noToken,
+        payer1: p.yes,
+        payer2: p.no,
+        receiver: p.ticket,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([w.kp]).rpc();
+      await BOTProgram.methods.redeemTicket(new anchor.BN(2)).accounts({
+        signer: w.kp.publicKey,
+        condition: cond.condition,
+        conditionAuthPda: cond.conditionAuthPda,
+        ticketTokenMint: cond.ticketTokenMint,
+        collateralVault: cond.collateralVault,
+        payer: p.ticket,
+        receiver: w.quote,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([w.kp]).rpc();
+
+      const stats = await conditionStats(cond);
+      expect(stats.ticketsMinted.toNumber()).to.equal(5);
+      expect(stats.ticketsSplit.toNumber()).to.equal(3);
+      expect(stats.ticketsMerged.toNumber()).to.equal(1);
+      expect(stats.ticketsRedeemed.toNumber()).to.equal(2);
+      expect(stats.openInterest.toNumber()).to.equal(3);
+      expect(stats.peakOpenInterest.toNumber()).to.equal(5);
+    });
+
+    it("Rejecting payouts of losing or missing tokens and counting the rest", async () => {
+      await resolveCondition(cond, 0).rpc();
+      await expectError(redeemPayout(cond, w, cond.noToken, p.no, 1).rpc(), "OutcomeTokenNotWinner");
+      await expectError(redeemPayout(cond, w, cond.yesToken, p.yes, 5).rpc(), "NotEnoughTokens");
+
+      await redeemPayout(cond, w, cond.yesToken, p.yes, 2).rpc();
+      const stats = await conditionStats(cond);
+      expect(stats.payoutRedeemed.toNumber()).to.equal(2);
+      expect(stats.openInterest.toNumber()).to.equal(1);
+    });
+  });
+
+  const BID = { bid: {} };
+  const ASK = { ask: {} };
+  const LIMIT = { limit: {} };
+  const CANCEL_TAKER = { cancelTaker: {} };
+
+  type TestMarket = {
+    market: anchor.web3.PublicKey;
+    marketAuthPda: anchor.web3.PublicKey;
+    orderbook1: anchor.web3.PublicKey;
+    orderbook2: anchor.web3.PublicKey;
+    balances: anchor.web3.PublicKey;
+    eventQueue: anchor.web3.PublicKey;
+    baseVault1: anchor.web3.PublicKey;
+    baseVault2: anchor.web3.PublicKey;
+    quoteVault: anchor.web3.PublicKey;
+    ticketVault: anchor.web3.PublicKey;
+    feeVault: anchor.web3.PublicKey;
+    cond: TestCondition;
+  };
+
+  // a market on a new condition, with the default lots prices go from 1 to 99 quote per outcome token
+  // markets end a week from now unless given an end time
+  async function createMarket(lots: number[] = [1, 1, 1], endTime: number | null = null): Promise<TestMarket> {
+    const [tickSize, baseLotSize, quoteLotSize] = lots.map((lot) => new anchor.BN(lot));
+    const cond = await createCondition();
+    const marketKeypair = new anchor.web3.Keypair();
+    const orderbook1 = new anchor.web3.Keypair();
+    const orderbook2 = new anchor.web3.Keypair();
+    const balances = new anchor.web3.Keypair();
+    const eventQueue = new anchor.web3.Keypair();
+    const [marketAuthPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [MARKET_AUTH_SEED, marketKeypair.publicKey.toBuffer()], EmberProgram.programId);
+
+    const end = new anchor.BN(endTime ?? Math.floor(Date.now() / 1000) + 7 * 24 * 60 * 60);
+    await EmberProgram.methods.initializeMarket(tickSize, baseLotSize, quoteLotSize, end).accounts({
+      signer: wallet,
+      config: programConfig,
+      market: marketKeypair.publicKey,
+      orderbookState1: orderbook1.publicKey,
+      orderbookState2: orderbook2.publicKey,
+      balances: balances.publicKey,
+      eventQueue: eventQueue.publicKey,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([orderbook1, orderbook2, balances, eventQueue, marketKeypair])
+      .preInstructions([
+        await EmberProgram.account.orderBookState.createInstruction(orderbook1),
+        await EmberProgram.account.orderBookState.createInstruction(orderbook2),
+        await EmberProgram.account.usersBalances.createInstruction(balances),
+        await EmberProgram.account.eventQueue.createInstruction(eventQueue),
+      ])
+      .rpc();
+
+    const [baseVault1, baseVault2, quoteVault, ticketVault, feeVault] = [0, 1, 2, 3, 4].map(() => new anchor.web3.Keypair());
+    await EmberProgram.methods.initializeVaults().accounts({
+      baseToken1: cond.yesToken,
+      baseToken2: cond.noToken,
+      quoteToken: collateralToken,
+      ticketToken: cond.ticketTokenMint,
+      baseVault1: baseVault1.publicKey,
+      baseVault2: baseVault2.publicKey,
+      quoteVault: quoteVault.publicKey,
+      ticketVault: ticketVault.publicKey,
+      feeVault: feeVault.publicKey,
+      market: marketKeypair.publicKey,
+      signer: wallet,
+      config: programConfig,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      condition: cond.condition,
+      marketAuthPda,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    }).signers([baseVault1, baseVault2, quoteVault, ticketVault, feeVault])
+      .rpc();
+
+    return {
+      market: marketKeypair.publicKey,
+      marketAuthPda,
+      orderbook1: orderbook1.publicKey,
+      orderbook2: orderbook2.publicKey,
+      balances: balances.publicKey,
+      eventQueue: eventQueue.publicKey,
+      baseVault1: baseVault1.publicKey,
+      baseVault2: baseVault2.publicKey,
+      quoteVault: quoteVault.publicKey,
+      ticketVault: ticketVault.publicKey,
+      feeVault: feeVault.publicKey,
+      cond,
+    };
+  }
+
+  type Trader = Wallet & { userAccount: anchor.web3.PublicKey };
+
+  async function createTrader(referrer: anchor.web3.PublicKey | null = null): Promise<Trader> {
+    const w = await createWallet();
+    const [userAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [w.kp.publicKey.toBuffer(), USER_ACCOUNT_PDA_SEED], EmberProgram.programId);
+    await EmberProgram.methods.createUserAccount().accounts({
+      signer: w.kp.publicKey,
+      market,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      userAccount,
+      referrer,
+    }).signers([w.kp]).rpc();
+    return { ...w, userAccount };
+  }
+
+  type MarketTrader = Trader & Positions & { marketUser: anchor.web3.PublicKey; uid: number };
+
+  // the trader's market account and outcome token accounts on the market
+  async function joinMarket(m: TestMarket, t: Trader): Promise<MarketTrader> {
+    const [marketUser] = anchor.web3.PublicKey.findProgramAddressSync(
+      [t.kp.publicKey.toBuffer(), m.market.toBuffer()], EmberProgram.programId);
+    await EmberProgram.methods.createMarketAccount().accounts({
+      signer: t.kp.publicKey,
+      market: m.market,
+      userAccount: t.userAccount,
+      userMarketPda: marketUser,
+      balances: m.balances,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      payer: t.kp.publicKey,
+    }).signers([t.kp]).rpc();
+    const positions = await createPositions(m.cond, t.kp.publicKey);
+    const uid = (await EmberProgram.account.marketSpecificUser.fetch(marketUser)).uid.toNumber();
+    return { ...t, ...positions, marketUser, uid };
+  }
+
+  function deposit(m: TestMarket, t: MarketTrader, quote: number, base1 = 0, base2 = 0) {
+    return EmberProgram.methods.depositBalance(new anchor.BN(quote), new anchor.BN(base1), new anchor.BN(base2)).accounts({
+      signer: t.kp.publicKey,
+      market: m.market,
+      userMarketPda: t.marketUser,
+      balances: m.balances,
+      baseVault1: m.baseVault1,
+      baseVault2: m.baseVault2,
+      quoteVault: m.quoteVault,
+      quoteAccount: t.quote,
+      baseAccount1: t.yes,
+      baseAccount2: t.no,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([t.kp]);
+  }
+
+  async function balanceOf(m: TestMarket, t: MarketTrader) {
+    const balance = (await EmberProgram.account.usersBalances.fetch(m.balances)).users[t.uid];
+    return {
+      quote: balance.quote.toNumber(),
+      base1: balance.base1.toNumber(),
+      base2: balance.base2.toNumber(),
+      rewards: balance.rewards.toNumber(),
+    };
+  }
+
+  async function orderCount(orderbook: anchor.web3.PublicKey) {
+    const state = await EmberProgram.account.orderBookState.fetch(orderbook);
+    return { bids: state.bids.leafCount.toNumber(), asks: state.asks.leafCount.toNumber() };
+  }
+
+  // the condition accounts used to mint or merge sets when orders on the two books match
+  function completeSet(m: TestMarket) {
+    return {
+      condition: m.cond.condition,
+      conditionAuthPda: m.cond.conditionAuthPda,
+      collateralVault: m.cond.collateralVault,
+      ticketTokenMint: m.cond.ticketTokenMint,
+      outcome1Token: m.cond.yesToken,
+      outcome2Token: m.cond.noToken,
+      ticketVault: m.ticketVault,
+      baseVault1: m.baseVault1,
+      baseVault2: m.baseVault2,
+      binaryOutcomeTokensProgram: BOTProgram.programId,
+    };
+  }
+
+  // book 1 trades the first outcome and book 2 the second one
+  function orderAccounts(m: TestMarket, t: MarketTrader, book: number) {
+    const [orderbook, otherOrderbook] = book == 1 ? [m.orderbook1, m.orderbook2] : [m.orderbook2, m.orderbook1];
+    return {
+      signer: t.kp.publicKey,
+      market: m.market,
+      orderbook,
+      otherOrderbook,
+      userAccount: t.userAccount,
+      userMarketPda: t.marketUser,
+      feeTiers: null,
+      referral: null,
+      balances: m.balances,
+      eventQueue: m.eventQueue,
+      marketAuthPda: m.marketAuthPda,
+      quoteVault: m.quoteVault,
+      feeVault: m.feeVault,
+      completeSet: completeSet(m),
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+  }
+
+  type OrderOptions = { orderType?: object; clientOrderId?: number; selfTradeBehavior?: object; expireIn?: number };
+
+  function limitOrder(m: TestMarket, t: MarketTrader, book: number, side: object, price: number, size: number, options: OrderOptions = {}) {
+    return EmberProgram.methods.placeLimitOrder({
+      side,
+      price: new anchor.BN(price),
+      size: new anchor.BN(size),
+      expireIn: new anchor.BN(options.expireIn ?? 0),
+      orderType: options.orderType ?? LIMIT,
+      clientOrderId: new anchor.BN(options.clientOrderId ?? 0),
+      selfTradeBehavior: options.selfTradeBehavior ?? CANCEL_TAKER,
+    }).accounts(orderAccounts(m, t, book)).signers([t.kp]);
+  }
+
+  describe("crossing limit orders", () => {
+    let m: TestMarket;
+    let maker: MarketTrader;
+    let taker: MarketTrader;
+
+    // the maker holds 5 of the first outcome and the taker 300 quote on the market
+    before(async () => {
+      m = await createMarket();
+      maker = await joinMarket(m, await createTrader());
+      taker = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, maker, maker, 5);
+      await deposit(m, maker, 0, 5).rpc();
+      await deposit(m, taker, 300).rpc();
+    });
+
+    it("Filling crossing limit orders against the book", async () => {
+      await limitOrder(m, maker, 1, ASK, 60, 3).rpc();
+      // fills at the resting price, the price improvement stays with the taker
+      await limitOrder(m, taker, 1, BID, 65, 2).rpc();
+
+      const balance = await balanceOf(m, taker);
+      expect(balance.base1).to.equal(2);
+      expect(balance.quote).to.equal(180);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 1 });
+
+      await consumeEvents(m).rpc();
+      expect((await balanceOf(m, maker)).quote).to.equal(120);
+    });
+
+    it("Rejecting limit orders the balance can't lock", async () => {
+      await expectError(limitOrder(m, taker, 1, BID, 60, 4).rpc(), "NotEnoughFunds");
+      await expectError(limitOrder(m, taker, 2, ASK, 60, 1).rpc(), "NotEnoughFunds");
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 1 });
+    });
+  });
+
+  async function bestOrder(orderbook: anchor.web3.PublicKey, side: object) {
+    const state = await EmberProgram.account.orderBookState.fetch(orderbook);
+    const book = side == BID ? state.bids : state.asks;
+    const node = book.nodes[book.bestOrderIdx.toNumber()];
+    return { orderId: node.key, price: node.order.price.toNumber(), size: node.order.size.toNumber() };
+  }
+
+  function cancelOrder(m: TestMarket, t: MarketTrader, book: number, side: object, orderId: anchor.BN) {
+    return EmberProgram.methods.cancelLimitOrder(side, orderId).accounts({
+      signer: t.kp.publicKey,
+      market: m.market,
+      orderbook: book == 1 ? m.orderbook1 : m.orderbook2,
+      userMarketPda: t.marketUser,
+      balances: m.balances,
+    }).signers([t.kp]);
+  }
+
+  describe("critbit order book", () => {
+    let m: TestMarket;
+    let maker: MarketTrader;
+    let taker: MarketTrader;
+
+    // the maker rests bids at 40, 55 and 50, the taker holds 2 of the first outcome
+    before(async () => {
+      m = await createMarket();
+      maker = await joinMarket(m, await createTrader());
+      taker = await joinMarket(m, await createTrader());
+      await deposit(m, maker, 200).rpc();
+      await limitOrder(m, maker, 1, BID, 40, 1).rpc();
+      await limitOrder(m, maker, 1, BID, 55, 1).rpc();
+      await limitOrder(m, maker, 1, BID, 50, 1).rpc();
+      await mintSets(m.cond, taker, taker, 2);
+      await deposit(m, taker, 0, 2).rpc();
+    });
+
+    it("Matching the best priced orders of the book first", async () => {
+      expect((await bestOrder(m.orderbook1, BID)).price).to.equal(55);
+      await limitOrder(m, taker, 1, ASK, 45, 2).rpc();
+
+      expect((await balanceOf(m, taker)).quote).to.equal(105);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 1, asks: 0 });
+      expect((await bestOrder(m.orderbook1, BID)).price).to.equal(40);
+    });
+
+    it("Cancelling an order and rejecting cancels of other users or missing orders", async () => {
+      const { orderId } = await bestOrder(m.orderbook1, BID);
+      await expectError(cancelOrder(m, taker, 1, BID, orderId).rpc(), "UnauthorizedOrderCancellation");
+      await cancelOrder(m, maker, 1, BID, orderId).rpc();
+      expect((await balanceOf(m, maker)).quote).to.equal(95);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 0 });
+
+      await expectError(cancelOrder(m, maker, 1, BID, orderId).rpc(), "OrderNotFound");
+      await expectError(cancelOrder(m, maker, 1, ASK, orderId).rpc(), "OrderNotFound");
+    });
+  });
+
+  const POST_ONLY = { postOnly: {} };
+  const POST_ONLY_SLIDE = { postOnlySlide: {} };
+  const IMMEDIATE_OR_CANCEL = { immediateOrCancel: {} };
+  const FILL_OR_KILL = { fillOrKill: {} };
+
+  describe("order types", () => {
+    let m: TestMarket;
+    let taker: MarketTrader;
+
+    // an ask of 2 at 60 rests on the book, the taker holds 500 quote
+    before(async () => {
+      m = await createMarket();
+      const maker = await joinMarket(m, await createTrader());
+      taker = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, maker, maker, 2);
+      await deposit(m, maker, 0, 2).rpc();
+      await deposit(m, taker, 500).rpc();
+      await limitOrder(m, maker, 1, ASK, 60, 2).rpc();
+    });
+
+    it("Rejecting post only orders that cross and immediate orders that can't fill", async () => {
+      await expectError(limitOrder(m, taker, 1, BID, 60, 1, { orderType: POST_ONLY }).rpc(), "PriceCrossesTheSpread");
+      await expectError(limitOrder(m, taker, 1, BID, 50, 1, { orderType: IMMEDIATE_OR_CANCEL }).rpc(), "ImmediateOrCancelNotFilled");
+      await expectError(limitOrder(m, taker, 1, BID, 60, 3, { orderType: FILL_OR_KILL }).rpc(), "FillOrKillNotFilled");
+      expect((await balanceOf(m, taker)).quote).to.equal(500);
+    });
+
+    it("Sliding post only orders and cancelling the rest of immediate orders", async () => {
+      // slides to one tick below the best ask instead of taking it
+      await limitOrder(m, taker, 1, BID, 65, 1, { orderType: POST_ONLY_SLIDE }).rpc();
+      expect((await bestOrder(m.orderbook1, BID)).price).to.equal(59);
+
+      await limitOrder(m, taker, 1, BID, 60, 3, { orderType: IMMEDIATE_OR_CANCEL }).rpc();
+      const balance = await balanceOf(m, taker);
+      expect(balance.base1).to.equal(2);
+      expect(balance.quote).to.equal(500 - 59 - 120);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 1, asks: 0 });
+    });
+  });
+
+  function cancelByClientId(m: TestMarket, t: MarketTrader, book: number, side: object, clientOrderId: number) {
+    return EmberProgram.methods.cancelOrderByClientId(side, new anchor.BN(clientOrderId)).accounts({
+      signer: t.kp.publicKey,
+      market: m.market,
+      orderbook: book == 1 ? m.orderbook1 : m.orderbook2,
+      userMarketPda: t.marketUser,
+      balances: m.balances,
+    }).signers([t.kp]);
+  }
+
+  function cancelAll(m: TestMarket, t: MarketTrader, side: object | null, otherOrderbook: anchor.web3.PublicKey | null) {
+    return EmberProgram.methods.cancelAllOrders(side).accounts({
+      signer: t.kp.publicKey,
+      market: m.market,
+      orderbook: m.orderbook1,
+      otherOrderbook,
+      userMarketPda: t.marketUser,
+      balances: m.balances,
+    }).signers([t.kp]);
+  }
+
+  describe("client order ids", () => {
+    let m: TestMarket;
+    let t: MarketTrader;
+
+    // bids with client ids 7 and 8 on the first book and 9 on the second one
+    before(async () => {
+      m = await createMarket();
+      t = await joinMarket(m, await createTrader());
+      await deposit(m, t, 300).rpc();
+      await limitOrder(m, t, 1, BID, 30, 2, { clientOrderId: 7 }).rpc();
+      await limitOrder(m, t, 1, BID, 20, 1, { clientOrderId: 8 }).rpc();
+      await limitOrder(m, t, 2, BID, 10, 1, { clientOrderId: 9 }).rpc();
+    });
+
+    it("Rejecting cancels of unknown client order ids and of the same book twice", async () => {
+      await expectError(cancelByClientId(m, t, 1, BID, 9).rpc(), "OrderNotFound");
+      await expectError(cancelByClientId(m, t, 1, ASK, 7).rpc(), "OrderNotFound");
+      await expectError(cancelAll(m, t, BID, m.orderbook1).rpc(), "SameOrderBook");
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 2, asks: 0 });
+    });
+
+    it("Cancelling orders by client order id and on both books at once", async () => {
+      expect((await balanceOf(m, t)).quote).to.equal(210);
+      await cancelByClientId(m, t, 1, BID, 7).rpc();
+      expect((await balanceOf(m, t)).quote).to.equal(270);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 1, asks: 0 });
+
+      await cancelAll(m, t, null, m.orderbook2).rpc();
+      expect((await balanceOf(m, t)).quote).to.equal(300);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 0 });
+      expect(await orderCount(m.orderbook2)).to.deep.equal({ bids: 0, asks: 0 });
+    });
+  });
+
+  const CANCEL_MAKER = { cancelMaker: {} };
+  const ABORT = { abort: {} };
+
+  describe("self trade prevention", () => {
+    let m: TestMarket;
+    let t: MarketTrader;
+
+    // the trader holds 200 quote and 2 of the first outcome, another user holds 1
+    before(async () => {
+      m = await createMarket();
+      t = await joinMarket(m, await createTrader());
+      const other = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, t, t, 2);
+      await deposit(m, t, 200, 2).rpc();
+      await mintSets(m.cond, other, other, 1);
+      await deposit(m, other, 0, 1).rpc();
+      await limitOrder(m, t, 1, ASK, 60, 1).rpc();
+      await limitOrder(m, other, 1, ASK, 62, 1).rpc();
+    });
+
+    it("Rejecting self trades with the abort behavior", async () => {
+      await expectError(limitOrder(m, t, 1, BID, 60, 1, { selfTradeBehavior: ABORT }).rpc(), "SelfTrade");
+
+      // the default cancels the incoming order without trading
+      await limitOrder(m, t, 1, BID, 60, 1).rpc();
+      expect((await balanceOf(m, t)).quote).to.equal(200);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 2 });
+    });
+
+    it("Cancelling the resting order of the taker on a self trade", async () => {
+      // the own ask is taken off the book and the bid fills against the next one
+      await limitOrder(m, t, 1, BID, 65, 1, { selfTradeBehavior: CANCEL_MAKER }).rpc();
+      // the own ask is refunded once its out event is consumed
+      const balance = await balanceOf(m, t);
+      expect(balance.base1).to.equal(2);
+      expect(balance.quote).to.equal(138);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 0 });
+      expect(await eventCount(m)).to.equal(2);
+    });
+  });
+
+  function marketOrderAccounts(m: TestMarket, t: MarketTrader, book: number) {
+    const [orderbook, otherOrderbook] = book == 1 ? [m.orderbook1, m.orderbook2] : [m.orderbook2, m.orderbook1];
+    return {
+      signer: t.kp.publicKey,
+      market: m.market,
+      userAccount: t.userAccount,
+      userMarketPda: t.marketUser,
+      feeTiers: null,
+      referral: null,
+      orderbook,
+      eventQueue: m.eventQueue,
+      marketAuthPda: m.marketAuthPda,
+      baseAccount: book == 1 ? t.yes : t.no,
+      quoteAccount: t.quote,
+      baseVault: book == 1 ? m.baseVault1 : m.baseVault2,
+      quoteVault: m.quoteVault,
+      feeVault: m.feeVault,
+      otherOrderbook,
+      completeSet: completeSet(m),
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+  }
+
+  function marketOrder(m: TestMarket, t: MarketTrader, book: number, side: object, amount: number, worstPrice: number, minFill: number) {
+    return EmberProgram.methods.placeMarketOrder(side, new anchor.BN(amount), new anchor.BN(worstPrice), new anchor.BN(minFill), CANCEL_TAKER)
+      .accounts(marketOrderAccounts(m, t, book)).signers([t.kp]);
+  }
+
+  describe("market orders", () => {
+    let m: TestMarket;
+    let taker: MarketTrader;
+
+    // asks of 1 at 60, 2 at 62 and 1 at 80 rest on the book
+    before(async () => {
+      m = await createMarket();
+      const maker = await joinMarket(m, await createTrader());
+      taker = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, maker, maker, 4);
+      await deposit(m, maker, 0, 4).rpc();
+      await limitOrder(m, maker, 1, ASK, 60, 1).rpc();
+      await limitOrder(m, maker, 1, ASK, 62, 2).rpc();
+      await limitOrder(m, maker, 1, ASK, 80, 1).rpc();
+    });
+
+    it("Settling market orders with the wallet's token accounts", async () => {
+      const before = await tokenAmount(taker.quote);
+      await marketOrder(m, taker, 1, BID, 2, 70, 2).rpc();
+      expect(await tokenAmount(taker.yes)).to.equal(2);
+      expect(await tokenAmount(taker.quote)).to.equal(before - 60 - 62);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 2 });
+    });
+
+    it("Rejecting market orders short of the minimum fill, on the wrong vault or overflowing", async () => {
+      // the ask at 80 is past the worst price
+      await expectError(marketOrder(m, taker, 1, BID, 2, 70, 2).rpc(), "MinimumFillNotReached");
+      await expectError(EmberProgram.methods.placeMarketOrder(BID, new anchor.BN(1), new anchor.BN(70), new anchor.BN(1), CANCEL_TAKER)
+        .accounts({ ...marketOrderAccounts(m, taker, 1), baseAccount: taker.no, baseVault: m.baseVault2 })
+        .signers([taker.kp]).rpc(), "InvalidMarket");
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 2 });
+
+      await expectError(limitOrder(m, taker, 1, BID, 2 ** 32 - 1, 2 ** 33).rpc(), "AmountOverflow");
+    });
+  });
+
+  function marketBidByQuote(m: TestMarket, t: MarketTrader, book: number, quoteBudget: number, worstPrice: number, minFill: number) {
+    return EmberProgram.methods.placeMarketBidByQuote(new anchor.BN(quoteBudget), new anchor.BN(worstPrice), new anchor.BN(minFill), CANCEL_TAKER)
+      .accounts(marketOrderAccounts(m, t, book)).signers([t.kp]);
+  }
+
+  describe("quote budget market bids", () => {
+    let m: TestMarket;
+    let taker: MarketTrader;
+
+    // an ask of 5 at 40 rests on the book
+    before(async () => {
+      m = await createMarket();
+      const maker = await joinMarket(m, await createTrader());
+      taker = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, maker, maker, 5);
+      await deposit(m, maker, 0, 5).rpc();
+      await limitOrder(m, maker, 1, ASK, 40, 5).rpc();
+    });
+
+    it("Buying as many lots as a quote budget pays for", async () => {
+      // 3 lots at 40, the unspent 10 stays in the wallet
+      const before = await tokenAmount(taker.quote);
+      await marketBidByQuote(m, taker, 1, 130, 99, 1).rpc();
+      expect(await tokenAmount(taker.yes)).to.equal(3);
+      expect(await tokenAmount(taker.quote)).to.equal(before - 120);
+      expect((await bestOrder(m.orderbook1, ASK)).size).to.equal(2);
+    });
+
+    it("Rejecting quote budgets that can't buy a lot within the worst price", async () => {
+      await expectError(marketBidByQuote(m, taker, 1, 39, 99, 1).rpc(), "MinimumFillNotReached");
+      await expectError(marketBidByQuote(m, taker, 1, 200, 30, 1).rpc(), "MinimumFillNotReached");
+      expect(await tokenAmount(taker.yes)).to.equal(3);
+    });
+  });
+
+  async function eventCount(m: TestMarket) {
+    return (await EmberProgram.account.eventQueue.fetch(m.eventQueue)).count.toNumber();
+  }
+
+  // the makers of the fill events in the queue, in order, to count their volume
+  function consumeEvents(m: TestMarket, makers: MarketTrader[] = [], limit = 10) {
+    const accounts: anchor.web3.AccountMeta[] = [];
+    makers.forEach((maker) => {
+      accounts.push(
+        { pubkey: maker.userAccount, isWritable: true, isSigner: false },
+        { pubkey: maker.marketUser, isWritable: true, isSigner: false },
+      );
+    });
+    return EmberProgram.methods.consumeEvents(new anchor.BN(limit)).accounts({
+      market: m.market,
+      eventQueue: m.eventQueue,
+      balances: m.balances,
+    }).remainingAccounts(accounts);
+  }
+
+  describe("event queue", () => {
+    let m: TestMarket;
+    let maker: MarketTrader;
+
+    // the maker's ask of 2 at 60 is filled by a taker bid
+    before(async () => {
+      m = await createMarket();
+      maker = await joinMarket(m, await createTrader());
+      const taker = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, maker, maker, 2);
+      await deposit(m, maker, 0, 2).rpc();
+      await deposit(m, taker, 200).rpc();
+      await limitOrder(m, maker, 1, ASK, 60, 2).rpc();
+      await limitOrder(m, taker, 1, BID, 60, 2).rpc();
+    });
+
+    it("Rejecting an event queue from another market", async () => {
+      const other = await createMarket();
+      await expectError(
+        EmberProgram.methods.consumeEvents(new anchor.BN(10)).accounts({
+          market: m.market,
+          eventQueue: other.eventQueue,
+          balances: m.balances,
+        }).rpc(),
+        "ConstraintRaw"
+      );
+      expect(await eventCount(m)).to.equal(1);
+    });
+
+    it("Settling fills on the maker's balance from the event queue", async () => {
+      expect((await balanceOf(m, maker)).quote).to.equal(0);
+      await consumeEvents(m).rpc();
+      expect(await eventCount(m)).to.equal(0);
+      expect((await balanceOf(m, maker)).quote).to.equal(120);
+    });
+  });
+
+  describe("tick and lot sizes", () => {
+    let m: TestMarket;
+    let t: MarketTrader;
+
+    // 10 native quote per quote lot, so a winning outcome token is worth 10 ticks
+    before(async () => {
+      m = await createMarket([1, 1, 10]);
+      t = await joinMarket(m, await createTrader());
+      await deposit(m, t, 10).rpc();
+    });
+
+    it("Pricing orders in ticks and quote lots", async () => {
+      expect((await EmberProgram.account.market.fetch(m.market)).maxPrice.toNumber()).to.equal(10);
+      expect(await tokenAmount(m.quoteVault)).to.equal(100);
+      await limitOrder(m, t, 1, BID, 6, 1).rpc();
+      expect((await balanceOf(m, t)).quote).to.equal(4);
+      expect((await bestOrder(m.orderbook1, BID)).price).to.equal(6);
+    });
+
+    it("Rejecting zero or uneven lot sizes and prices past the max price", async () => {
+      await expectError(createMarket([0, 1, 1]), "InvalidLotSizes");
+      // 100 native per winning token doesn't split into quote lots of 30
+      await expectError(createMarket([1, 1, 30]), "InvalidLotSizes");
+
+      await expectError(limitOrder(m, t, 1, BID, 10, 1).rpc(), "InvalidPrice");
+      await expectError(limitOrder(m, t, 1, BID, 0, 1).rpc(), "InvalidPrice");
+      expect((await balanceOf(m, t)).quote).to.equal(4);
+    });
+  });
+
+  describe("complete set matching", () => {
+    let m: TestMarket;
+    let yesBuyer: MarketTrader;
+    let noBuyer: MarketTrader;
+
+    // both buyers hold 100 quote, a YES bid of 1 at 60 rests on the first book
+    before(async () => {
+      m = await createMarket();
+      yesBuyer = await joinMarket(m, await createTrader());
+      noBuyer = await joinMarket(m, await createTrader());
+      await deposit(m, yesBuyer, 100).rpc();
+      await deposit(m, noBuyer, 100).rpc();
+      await limitOrder(m, yesBuyer, 1, BID, 60, 1).rpc();
+    });
+
+    it("Rejecting orders given the same book as the other orderbook", async () => {
+      await expectError(
+        limitOrder(m, noBuyer, 1, BID, 40, 1).accounts({ ...orderAccounts(m, noBuyer, 1), otherOrderbook: m.orderbook1 }).rpc(),
+        "ConstraintRaw"
+      );
+      expect((await balanceOf(m, noBuyer)).quote).to.equal(100);
+    });
+
+    it("Minting an outcome set for bids matched across the two books", async () => {
+      // a NO bid at 40 meets the YES bid at 60, together they pay for a new set
+      await limitOrder(m, noBuyer, 2, BID, 40, 1).rpc();
+      const balance = await balanceOf(m, noBuyer);
+      expect(balance.base2).to.equal(1);
+      expect(balance.quote).to.equal(60);
+      expect(await tokenAmount(m.cond.collateralVault)).to.equal(100);
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 0 });
+
+      await consumeEvents(m).rpc();
+      expect((await balanceOf(m, yesBuyer)).base1).to.equal(1);
+    });
+  });
+
+  function syntheticAsk(m: TestMarket, t: MarketTrader, price: number, size: number) {
+    return EmberProgram.methods.placeSyntheticAsk(new anchor.BN(price), new anchor.BN(size), new anchor.BN(0), LIMIT, new anchor.BN(0), CANCEL_TAKER)
+      .accounts(orderAccounts(m, t, 1)).signers([t.kp]);
+  }
+
+  describe("synthetic asks", () => {
+    let m: TestMarket;
+    let seller: MarketTrader;
+    let buyer: MarketTrader;
+
+    // seller and buyer hold 100 quote each and no outcome tokens
+    before(async () => {
+      m = await createMarket();
+      seller = await joinMarket(m, await createTrader());
+      buyer = await joinMarket(m, await createTrader());
+      await deposit(m, seller, 100).rpc();
+      await deposit(m, buyer, 100).rpc();
+    });
+
+    it("Rejecting synthetic asks priced outside the book or above the balance", async () => {
+      await expectError(syntheticAsk(m, seller, 100, 1).rpc(), "InvalidPrice");
+      await expectError(syntheticAsk(m, seller, 0, 1).rpc(), "InvalidPrice");
+      await expectError(syntheticAsk(m, seller, 10, 2).rpc(), "NotEnoughFunds");
+      expect((await balanceOf(m, seller)).quote).to.equal(100);
+    });
+
+    it("Selling YES short with a synthetic ask resting as a NO bid", async () => {
+      // an ask at 70 on YES is a bid at 30 on NO
+      await syntheticAsk(m, seller, 70, 1).rpc();
+      expect((await balanceOf(m, seller)).quote).to.equal(70);
+      expect((await bestOrder(m.orderbook2, BID)).price).to.equal(30);
+
+      await limitOrder(m, buyer, 1, BID, 70, 1).rpc();
+      const balance = await balanceOf(m, buyer);
+      expect(balance.base1).to.equal(1);
+      expect(balance.quote).to.equal(30);
+
+      await consumeEvents(m).rpc();
+      expect((await balanceOf(m, seller)).base2).to.equal(1);
+    });
+  });
+
+  function setMarketFees(m: TestMarket, makerFeeBps: number, takerFeeBps: number, signer: Wallet | null = null) {
+    const builder = EmberProgram.methods.setMarketFees(new anchor.BN(makerFeeBps), new anchor.BN(takerFeeBps)).accounts({
+      signer: signer ? signer.kp.publicKey : wallet,
+      config: programConfig,
+      market: m.market,
+    });
+    return signer ? builder.signers([signer.kp]) : builder;
+  }
+
+  function withdrawFees(m: TestMarket, signer: Wallet | null = null) {
+    const builder = EmberProgram.methods.withdrawFees().accounts({
+      signer: signer ? signer.kp.publicKey : wallet,
+      config: programConfig,
+      market: m.market,
+      marketAuthPda: m.marketAuthPda,
+      feeVault: m.feeVault,
+      receiver: collateralTokenAta,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+    return signer ? builder.signers([signer.kp]) : builder;
+  }
+
+  describe("market fees", () => {
+    let m: TestMarket;
+    let maker: MarketTrader;
+    let taker: MarketTrader;
+
+    // a 50 bps maker fee and 1% taker fee, the maker holds 10 of the first outcome and 300 quote
+    before(async () => {
+      m = await createMarket();
+      await setMarketFees(m, 50, 100).rpc();
+      maker = await joinMarket(m, await createTrader());
+      taker = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, maker, maker, 10);
+      await deposit(m, maker, 300, 10).rpc();
+      await deposit(m, taker, 600).rpc();
+    });
+
+    it("Rejecting fees over the cap and fee changes by non admins", async () => {
+      await expectError(setMarketFees(m, 0, 2000).rpc(), "InvalidFees");
+      // a maker rebate is paid out of the taker fee
+      await expectError(setMarketFees(m, -50, 10).rpc(), "InvalidFees");
+
+      const outsider = await createWallet();
+      await expectError(setMarketFees(m, 0, 100, outsider).rpc(), "InvalidAdmin");
+      await expectError(withdrawFees(m, outsider).rpc(), "InvalidAdmin");
+    });
+
+    it("Charging the taker fee and withdrawing it from the fee vault", async () => {
+      await limitOrder(m, maker, 1, ASK, 50, 10).rpc();
+      // 1% of the 500 the fill cost, the maker pays 50 bps of it when the fill is consumed
+      await limitOrder(m, taker, 1, BID, 50, 10).rpc();
+      expect((await balanceOf(m, taker)).quote).to.equal(95);
+      expect(await tokenAmount(m.feeVault)).to.equal(7);
+      await consumeEvents(m).rpc();
+      expect((await balanceOf(m, maker)).quote).to.equal(300 + 500 - 2);
+
+      const before = await tokenAmount(collateralTokenAta);
+      await withdrawFees(m).rpc();
+      expect(await tokenAmount(collateralTokenAta)).to.equal(before + 7);
+      expect(await tokenAmount(m.feeVault)).to.equal(0);
+    });
+
+    it("Releasing the whole maker fee lock of a bid filled in parts", async () => {
+      // 201 quote locks a fee of 1, the partial fills round their fees down to 0
+      const start = (await balanceOf(m, maker)).quote;
+      await limitOrder(m, maker, 1, BID, 67, 3).rpc();
+      expect((await balanceOf(m, maker)).quote).to.equal(start - 202);
+
+      await limitOrder(m, taker, 1, ASK, 67, 1).rpc();
+      await limitOrder(m, taker, 1, ASK, 67, 2).rpc();
+      await consumeEvents(m).rpc();
+      const balance = await balanceOf(m, maker);
+      expect(balance.base1).to.equal(3);
+      expect(balance.quote).to.equal(start - 201);
+    });
+  });
+
+  const [feeTiers] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("fee_tiers")], EmberProgram.programId);
+
+  function setFeeTiers(tiers: { minVolume: number; makerDiscountBps: number; takerDiscountBps: number }[], signer: Wallet | null = null) {
+    const builder = EmberProgram.methods.setFeeTiers(tiers.map((tier) => ({
+      minVolume: new anchor.BN(tier.minVolume),
+      makerDiscountBps: new anchor.BN(tier.makerDiscountBps),
+      takerDiscountBps: new anchor.BN(tier.takerDiscountBps),
+    }))).accounts({
+      signer: signer ? signer.kp.publicKey : wallet,
+      config: programConfig,
+      feeTiers,
+    });
+    return signer ? builder.signers([signer.kp]) : builder;
+  }
+
+  describe("volume and fee tiers", () => {
+    let m: TestMarket;
+    let maker: MarketTrader;
+    let taker: MarketTrader;
+
+    function tieredBid(size: number) {
+      return limitOrder(m, taker, 1, BID, 50, size).accounts({ ...orderAccounts(m, taker, 1), feeTiers });
+    }
+
+    // a 1% taker fee waived from 500 of trailing volume, an ask of 20 at 50 rests on the book
+    before(async () => {
+      await EmberProgram.methods.initializeFeeTiers().accounts({
+        signer: wallet,
+        config: programConfig,
+        feeTiers,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).rpc();
+      await setFeeTiers([{ minVolume: 500, makerDiscountBps: 0, takerDiscountBps: 10_000 }]).rpc();
+
+      m = await createMarket();
+      await setMarketFees(m, 0, 100).rpc();
+      maker = await joinMarket(m, await createTrader());
+      taker = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, maker, maker, 20);
+      await deposit(m, maker, 0, 20).rpc();
+      await deposit(m, taker, 1100).rpc();
+      await limitOrder(m, maker, 1, ASK, 50, 20).rpc();
+    });
+
+    after(async () => {
+      await setFeeTiers([]).rpc();
+    });
+
+    it("Rejecting unsorted fee tiers, discounts over 100% and non admins", async () => {
+      await expectError(setFeeTiers([
+        { minVolume: 1000, makerDiscountBps: 0, takerDiscountBps: 100 },
+        { minVolume: 500, makerDiscountBps: 0, takerDiscountBps: 200 },
+      ]).rpc(), "InvalidFeeTiers");
+      await expectError(setFeeTiers([{ minVolume: 0, makerDiscountBps: 10_001, takerDiscountBps: 0 }]).rpc(), "InvalidFeeTiers");
+      await expectError(setFeeTiers([], await createWallet()).rpc(), "InvalidAdmin");
+    });
+
+    it("Discounting the taker fee by trailing volume", async () => {
+      // the first fill pays the full fee and brings the trailing volume to the tier
+      await tieredBid(10).rpc();
+      expect((await balanceOf(m, taker)).quote).to.equal(595);
+      await tieredBid(10).rpc();
+      expect((await balanceOf(m, taker)).quote).to.equal(95);
+      expect(await tokenAmount(m.feeVault)).to.equal(5);
+      expect((await EmberProgram.account.user.fetch(taker.userAccount)).volume.toNumber()).to.equal(1000);
+    });
+
+    it("Counting maker volume for the fills given maker accounts and settling the rest", async () => {
+      await expectError(consumeEvents(m, [taker]).rpc(), "InvalidMakerAccounts");
+
+      // only the first fill comes with the maker's accounts, the second one is settled all the same
+      await consumeEvents(m, [maker]).rpc();
+      expect(await eventCount(m)).to.equal(0);
+      expect((await balanceOf(m, maker)).quote).to.equal(1000);
+      expect((await EmberProgram.account.marketSpecificUser.fetch(maker.marketUser)).volume.toNumber()).to.equal(500);
+    });
+  });
+
+  function sleep(ms: number) {
+    return new Promise((resolve) => setTimeout(resolve, ms));
+  }
+
+  async function initializeRewards(m: TestMarket, rewardToken: anchor.web3.PublicKey, multiplier: number, maxSpread: number) {
+    const rewardVault = new anchor.web3.Keypair();
+    await EmberProgram.methods.initializeRewards(new anchor.BN(multiplier), new anchor.BN(maxSpread)).accounts({
+      signer: wallet,
+      config: programConfig,
+      market: m.market,
+      marketAuthPda: m.marketAuthPda,
+      rewardToken,
+      rewardVault: rewardVault.publicKey,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    }).signers([rewardVault]).rpc();
+    return rewardVault.publicKey;
+  }
+
+  function sampleRewards(m: TestMarket) {
+    return EmberProgram.methods.sampleRewards().accounts({
+      market: m.market,
+      orderbookState1: m.orderbook1,
+      orderbookState2: m.orderbook2,
+      balances: m.balances,
+    });
+  }
+
+  describe("liquidity rewards", () => {
+    let m: TestMarket;
+    let rewardToken: anchor.web3.PublicKey;
+
+    before(async () => {
+      m = await createMarket();
+      rewardToken = await createMint(connection, payer, payer.publicKey, null, 0);
+    });
+
+    it("Rejecting reward multipliers under the minimum and markets without rewards", async () => {
+      await expectError(initializeRewards(m, rewardToken, 50, 10), "RewardsMultiplierTooSmall");
+      await expectError(EmberProgram.methods.setRewards(new anchor.BN(1_000), new anchor.BN(10)).accounts({
+        signer: wallet,
+        config: programConfig,
+        market: m.market,
+      }).rpc(), "InvalidMarket");
+    });
+
+    it("Paying liquidity rewards to orders quoting around the midpoint", async () => {
+      const rewardVault = await initializeRewards(m, rewardToken, 1_000_000, 10);
+      await mintTo(connection, payer, rewardToken, rewardVault, payer, 1_000_000);
+
+      const t = await joinMarket(m, await createTrader());
+      const wide = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, t, t, 10);
+      await deposit(m, t, 490, 10).rpc();
+      await deposit(m, wide, 300).rpc();
+      await limitOrder(m, t, 1, BID, 49, 10).rpc();
+      await limitOrder(m, t, 1, ASK, 51, 10).rpc();
+      // 20 ticks under the midpoint, past the max spread
+      await limitOrder(m, wide, 1, BID, 30, 10).rpc();
+
+      await sleep(3000);
+      await sampleRewards(m).rpc();
+      const { rewards } = await balanceOf(m, t);
+      expect(rewards).to.be.greaterThan(0);
+      expect((await balanceOf(m, wide)).rewards).to.equal(0);
+
+      const receiver = await createAccount(connection, payer, rewardToken, t.kp.publicKey);
+      await EmberProgram.methods.claimRewards().accounts({
+        signer: t.kp.publicKey,
+        market: m.market,
+        userMarketPda: t.marketUser,
+        balances: m.balances,
+        marketAuthPda: m.marketAuthPda,
+        rewardVault,
+        receiver,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([t.kp]).rpc();
+      expect(await tokenAmount(receiver)).to.equal(rewards);
+      expect((await balanceOf(m, t)).rewards).to.equal(0);
+    });
+  });
+
+  function referralPda(m: TestMarket, referrer: Trader) {
+    const [referral] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("referral"), referrer.userAccount.toBuffer(), m.market.toBuffer()], EmberProgram.programId);
+    return referral;
+  }
+
+  function setReferralShare(m: TestMarket, shareBps: number) {
+    return EmberProgram.methods.setReferralShare(new anchor.BN(shareBps)).accounts({
+      signer: wallet,
+      config: programConfig,
+      market: m.market,
+    });
+  }
+
+  function claimReferralFees(m: TestMarket, signer: Trader, referral: anchor.web3.PublicKey) {
+    return EmberProgram.methods.claimReferralFees().accounts({
+      signer: signer.kp.publicKey,
+      market: m.market,
+      userAccount: signer.userAccount,
+      referral,
+      marketAuthPda: m.marketAuthPda,
+      quoteVault: m.quoteVault,
+      receiver: signer.quote,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([signer.kp]);
+  }
+
+  describe("referrals", () => {
+    let m: TestMarket;
+    let referrer: Trader;
+    let taker: MarketTrader;
+    let referral: anchor.web3.PublicKey;
+
+    function referredBid(size: number, withReferral: boolean) {
+      return limitOrder(m, taker, 1, BID, 50, size)
+        .accounts({ ...orderAccounts(m, taker, 1), referral: withReferral ? referral : null });
+    }
+
+    // a 1% taker fee shared half with the referrer, an ask of 30 at 50 rests on the book
+    before(async () => {
+      m = await createMarket();
+      await setMarketFees(m, 0, 100).rpc();
+      await setReferralShare(m, 5_000).rpc();
+      referrer = await createTrader();
+      referral = referralPda(m, referrer);
+
+      const maker = await joinMarket(m, await createTrader());
+      taker = await joinMarket(m, await createTrader(referrer.userAccount));
+      await mintSets(m.cond, maker, maker, 30);
+      await deposit(m, maker, 0, 30).rpc();
+      await deposit(m, taker, 1515).rpc();
+      await limitOrder(m, maker, 1, ASK, 50, 30).rpc();
+    });
+
+    it("Rejecting referral shares over 100%", async () => {
+      await expectError(setReferralShare(m, 10_001).rpc(), "InvalidReferralShare");
+    });
+
+    it("Keeping the referrer's share in the fee vault until its referral account exists", async () => {
+      await referredBid(10, false).rpc();
+      expect(await tokenAmount(m.feeVault)).to.equal(5);
+      expect((await balanceOf(m, taker)).quote).to.equal(1010);
+    });
+
+    it("Sharing the taker fee of referred users with their referrer", async () => {
+      // anyone can open the referrer's referral account, here the referred user does
+      await EmberProgram.methods.createReferralAccount().accounts({
+        signer: taker.kp.publicKey,
+        market: m.market,
+        userAccount: referrer.userAccount,
+        referral,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      }).signers([taker.kp]).rpc();
+
+      // half of the 10 taker fee stays in the quote vault for the referrer
+      await referredBid(20, true).rpc();
+      expect(await tokenAmount(m.feeVault)).to.equal(10);
+      const fees = await EmberProgram.account.referralFees.fetch(referral);
+      expect(fees.fees.toNumber()).to.equal(5);
+      expect(fees.referredVolume.toNumber()).to.equal(1000);
+
+      await expectError(claimReferralFees(m, taker, referral).rpc(), "ConstraintSeeds");
+      const before = await tokenAmount(referrer.quote);
+      await claimReferralFees(m, referrer, referral).rpc();
+      expect(await tokenAmount(referrer.quote)).to.equal(before + 5);
+      expect((await EmberProgram.account.referralFees.fetch(referral)).fees.toNumber()).to.equal(0);
+    });
+  });
+
+  async function marketState(m: TestMarket) {
+    return await EmberProgram.account.market.fetch(m.market);
+  }
+
+  function closeMarket(m: TestMarket) {
+    return EmberProgram.methods.closeMarket().accounts({ market: m.market });
+  }
+
+  function syncResolution(m: TestMarket) {
+    return EmberProgram.methods.syncResolution().accounts({ market: m.market, condition: m.cond.condition });
+  }
+
+  describe("market lifecycle", () => {
+    let m: TestMarket;
+    let t: MarketTrader;
+
+    // an open market a week from its end time, the trader has a bid resting on it
+    before(async () => {
+      m = await createMarket();
+      t = await joinMarket(m, await createTrader());
+      await deposit(m, t, 100).rpc();
+      await limitOrder(m, t, 1, BID, 40, 1).rpc();
+    });
+
+    it("Rejecting short markets, early closes and syncing unresolved conditions", async () => {
+      await expectError(createMarket([1, 1, 1], Math.floor(Date.now() / 1000) + 60), "DurationTooShort");
+      await expectError(closeMarket(m).rpc(), "MarketNotEnded");
+      await expectError(syncResolution(m).rpc(), "ConditionNotResolved");
+      expect((await marketState(m)).status).to.deep.equal({ open: {} });
+    });
+
+    it("Resolving a market whose condition resolved before its end time", async () => {
+      await resolveCondition(m.cond, 0).rpc();
+      await syncResolution(m).rpc();
+      const state = await marketState(m);
+      expect(state.status).to.deep.equal({ resolved: {} });
+      expect(state.winningOutcome.toNumber()).to.equal(1);
+
+      // no more orders, but the resting bid can still be cancelled
+      await expectError(limitOrder(m, t, 1, BID, 40, 1).rpc(), "MarketClosed");
+      await cancelAll(m, t, null, m.orderbook2).rpc();
+      expect((await balanceOf(m, t)).quote).to.equal(100);
+    });
+  });
+
+  function settleMarket(m: TestMarket, limit: number) {
+    return EmberProgram.methods.settleMarket(new anchor.BN(limit)).accounts({
+      market: m.market,
+      orderbookState1: m.orderbook1,
+      orderbookState2: m.orderbook2,
+      balances: m.balances,
+      eventQueue: m.eventQueue,
+      marketAuthPda: m.marketAuthPda,
+      quoteVault: m.quoteVault,
+      completeSet: completeSet(m),
+      group: null,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+  }
+
+  function claimBalance(m: TestMarket, t: MarketTrader) {
+    return EmberProgram.methods.claimBalance().accounts({
+      signer: t.kp.publicKey,
+      market: m.market,
+      userMarketPda: t.marketUser,
+      balances: m.balances,
+      marketAuthPda: m.marketAuthPda,
+      baseVault1: m.baseVault1,
+      baseVault2: m.baseVault2,
+      quoteVault: m.quoteVault,
+      quoteAccount: t.quote,
+      baseAccount1: t.yes,
+      baseAccount2: t.no,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([t.kp]);
+  }
+
+  describe("market settlement", () => {
+    let m: TestMarket;
+    let seller: MarketTrader;
+    let buyer: MarketTrader;
+
+    // the seller asks 3 YES at 60, the buyer takes 2 of them, YES wins
+    before(async () => {
+      m = await createMarket();
+      seller = await joinMarket(m, await createTrader());
+      buyer = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, seller, seller, 3);
+      await deposit(m, seller, 0, 3).rpc();
+      await deposit(m, buyer, 300).rpc();
+      await limitOrder(m, seller, 1, ASK, 60, 3).rpc();
+      await limitOrder(m, buyer, 1, BID, 60, 2).rpc();
+    });
+
+    it("Rejecting settlement before resolution and with pending events, and claims while it's in progress", async () => {
+      await expectError(settleMarket(m, 10).rpc(), "MarketNotResolved");
+      await resolveCondition(m.cond, 0).rpc();
+      await syncResolution(m).rpc();
+      await expectError(settleMarket(m, 10).rpc(), "EventsPending");
+      await consumeEvents(m, [seller]).rpc();
+
+      // cancels the ask left on the book and settles the seller, the buyer's YES lots aren't converted yet
+      await settleMarket(m, 2).rpc();
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 0 });
+      await expectError(claimBalance(m, buyer).rpc(), "SettlementInProgress");
+      await expectError(deposit(m, buyer, 10).rpc(), "MarketSettled");
+    });
+
+    it("Settling a resolved market and claiming the winnings", async () => {
+      // every YES lot is paid the max price
+      await settleMarket(m, 10).rpc();
+      expect((await marketState(m)).settled).to.be.true;
+      await expectError(settleMarket(m, 10).rpc(), "MarketSettled");
+      expect(await balanceOf(m, buyer)).to.deep.equal({ quote: 380, base1: 0, base2: 0, rewards: 0 });
+      expect(await balanceOf(m, seller)).to.deep.equal({ quote: 220, base1: 0, base2: 0, rewards: 0 });
+
+      const before = await tokenAmount(buyer.quote);
+      await claimBalance(m, buyer).rpc();
+      await claimBalance(m, seller).rpc();
+      expect(await tokenAmount(buyer.quote)).to.equal(before + 380);
+      expect(await tokenAmount(m.quoteVault)).to.equal(0);
+    });
+  });
+
+  type MarketParams = { question?: string; endTime?: number; marketResolves?: boolean; creatorFeeBps?: number };
+
+  function marketPda(seed: string, market: anchor.web3.PublicKey) {
+    const [pda] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from(seed), market.toBuffer()], EmberProgram.programId);
+    return pda;
+  }
+
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+
+  // the provider wallet, it's the admin
+  function providerWallet(): Wallet {
+    return { kp: (provider.wallet as anchor.Wallet).payer, quote: collateralTokenAta };
+  }
+
+  // create_market_with_condition has too many accounts for a legacy transaction, it's sent as a versioned
+  // transaction with the accounts that don't sign in a lookup table. non admin creators pay the bond from bondPayer
+  async function createMarketWithCondition(creator: Wallet, params: MarketParams = {}, bondPayer: anchor.web3.PublicKey | null = null): Promise<TestMarket> {
+    const marketKeypair = new anchor.web3.Keypair();
+    const orderbook1 = new anchor.web3.Keypair();
+    const orderbook2 = new anchor.web3.Keypair();
+    const balances = new anchor.web3.Keypair();
+    const eventQueue = new anchor.web3.Keypair();
+    const conditionKeypair = new anchor.web3.Keypair();
+    const collateralVault = new anchor.web3.Keypair();
+    await provider.sendAndConfirm(new anchor.web3.Transaction().add(
+      await EmberProgram.account.orderBookState.createInstruction(orderbook1),
+      await EmberProgram.account.orderBookState.createInstruction(orderbook2),
+      await EmberProgram.account.usersBalances.createInstruction(balances),
+      await EmberProgram.account.eventQueue.createInstruction(eventQueue),
+    ), [orderbook1, orderbook2, balances, eventQueue]);
+
+    const market = marketKeypair.publicKey;
+    const [conditionAuthPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [CONDITION_AUTH_PDA_SEED, conditionKeypair.publicKey.toBuffer()], BOTProgram.programId);
+    const m: TestMarket = {
+      market,
+      marketAuthPda: marketPda("market_auth_seed", market),
+      orderbook1: orderbook1.publicKey,
+      orderbook2: orderbook2.publicKey,
+      balances: balances.publicKey,
+      eventQueue: eventQueue.publicKey,
+      baseVault1: marketPda("base_vault_1", market),
+      baseVault2: marketPda("base_vault_2", market),
+      quoteVault: marketPda("quote_vault", market),
+      ticketVault: marketPda("ticket_vault", market),
+      feeVault: marketPda("fee_vault", market),
+      cond: {
+        condition: conditionKeypair.publicKey,
+        conditionAuthPda,
+        ticketTokenMint: marketPda("ticket_mint", market),
+        yesToken: marketPda("outcome_1_mint", market),
+        noToken: marketPda("outcome_2_mint", market),
+        collateralVault: collateralVault.publicKey,
+      },
+    };
+
+    const ix = await EmberProgram.methods.createMarketWithCondition({
+      question: params.question ?? "Will it rain tomorrow?",
+      conditionName: "rain",
+      outcome1Name: "yes",
+      outcome2Name: "no",
+      collateralPerTicket: new anchor.BN(100),
+      endTime: new anchor.BN(params.endTime ?? Math.floor(Date.now() / 1000) + 7 * 24 * 60 * 60),
+      tickSize: new anchor.BN(1),
+      baseLotSize: new anchor.BN(1),
+      quoteLotSize: new anchor.BN(1),
+      marketResolves: params.marketResolves ?? false,
+      creatorFeeBps: new anchor.BN(params.creatorFeeBps ?? 0),
+    }).accounts({
+      signer: creator.kp.publicKey,
+      config: programConfig,
+      market,
+      orderbookState1: m.orderbook1,
+      orderbookState2: m.orderbook2,
+      balances: m.balances,
+      eventQueue: m.eventQueue,
+      marketAuthPda: m.marketAuthPda,
+      condition: m.cond.condition,
+      conditionAuthPda,
+      collateralVault: m.cond.collateralVault,
+      ticketTokenMint: m.cond.ticketTokenMint,
+      outcomeToken1: m.cond.yesToken,
+      outcomeToken2: m.cond.noToken,
+      quoteToken: collateralToken,
+      baseVault1: m.baseVault1,
+      baseVault2: m.baseVault2,
+      quoteVault: m.quoteVault,
+      ticketVault: m.ticketVault,
+      feeVault: m.feeVault,
+      bondVault: marketPda("bond_vault", market),
+      bondPayer,
+      binaryOutcomeTokensProgram: BOTProgram.programId,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    }).instruction();
+
+    const [createTableIx, lookupTable] = anchor.web3.AddressLookupTableProgram.createLookupTable({
+      authority: wallet,
+      payer: wallet,
+      recentSlot: await connection.getSlot("finalized"),
+    });
+    const extendTableIx = anchor.web3.AddressLookupTableProgram.extendLookupTable({
+      authority: wallet,
+      payer: wallet,
+      lookupTable,
+      addresses: ix.keys.filter((key) => !key.isSigner).map((key) => key.pubkey),
+    });
+    await provider.sendAndConfirm(new anchor.web3.Transaction().add(createTableIx, extendTableIx));
+    // the addresses can be looked up from the slot after the extension on
+    const extendedAt = await connection.getSlot();
+    while ((await connection.getSlot()) <= extendedAt) {
+      await sleep(200);
+    }
+    const table = (await connection.getAddressLookupTable(lookupTable)).value;
+
+    const { blockhash, lastValidBlockHeight } = await connection.getLatestBlockhash();
+    const tx = new anchor.web3.VersionedTransaction(new anchor.web3.TransactionMessage({
+      payerKey: creator.kp.publicKey,
+      recentBlockhash: blockhash,
+      instructions: [anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 }), ix],
+    }).compileToV0Message([table]));
+    tx.sign([creator.kp, marketKeypair, conditionKeypair, collateralVault]);
+    const signature = await connection.sendTransaction(tx);
+    await connection.confirmTransaction({ signature, blockhash, lastValidBlockHeight });
+    return m;
+  }
+
+  function resolveMarket(m: TestMarket, outcome: number, signer: Wallet = providerWallet()) {
+    return EmberProgram.methods.resolveMarket(new anchor.BN(outcome)).accounts({
+      signer: signer.kp.publicKey,
+      config: programConfig,
+      market: m.market,
+      marketAuthPda: m.marketAuthPda,
+      condition: m.cond.condition,
+      conditionAuthPda: m.cond.conditionAuthPda,
+      group: null,
+      binaryOutcomeTokensProgram: BOTProgram.programId,
+    }).signers([signer.kp]);
+  }
+
+  describe("markets created with their condition", () => {
+    let m: TestMarket;
+    let resolvedByMarket: TestMarket;
+
+    before(async () => {
+      m = await createMarketWithCondition(providerWallet());
+      resolvedByMarket = await createMarketWithCondition(providerWallet(), { marketResolves: true });
+    });
+
+    it("Rejecting market creations with a long question, a short duration or a non-admin creator", async () => {
+      await expectError(createMarketWithCondition(providerWallet(), { question: "?".repeat(201) }), "QuestionTooLong");
+      const endTime = Math.floor(Date.now() / 1000) + 60;
+      await expectError(createMarketWithCondition(providerWallet(), { endTime }), "DurationTooShort");
+      await expectError(createMarketWithCondition(await createWallet()), "InvalidAdmin");
+      // the condition of m is resolved by the admin wallet, not by the market
+      await expectError(resolveMarket(m, 0).rpc(), "ConstraintRaw");
+      await expectError(resolveMarket(resolvedByMarket, 0, await createWallet()).rpc(), "InvalidAdmin");
+    });
+
+    it("Creating a market together with its condition", async () => {
+      const state = await marketState(m);
+      expect(Buffer.from(state.question).toString()).to.equal("Will it rain tomorrow?");
+      expect(state.conditionKey.toBase58()).to.equal(m.cond.condition.toBase58());
+      expect(state.maxPrice.toNumber()).to.equal(100);
+      const condition = await BOTProgram.account.condition.fetch(m.cond.condition);
+      expect(condition.resolutionAuth.toBase58()).to.equal(wallet.toBase58());
+
+      // the market trades like one put together from its parts
+      const t = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, t, t, 2);
+      expect(await tokenAmount(t.yes)).to.equal(2);
+      await deposit(m, t, 100, 2).rpc();
+      await limitOrder(m, t, 1, ASK, 60, 2).rpc();
+      await limitOrder(m, t, 2, BID, 30, 1).rpc();
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 0, asks: 1 });
+      expect(await orderCount(m.orderbook2)).to.deep.equal({ bids: 1, asks: 0 });
+    });
+
+    it("Resolving a market through the condition it resolves", async () => {
+      const condition = await BOTProgram.account.condition.fetch(resolvedByMarket.cond.condition);
+      expect(condition.resolutionAuth.toBase58()).to.equal(resolvedByMarket.marketAuthPda.toBase58());
+
+      await resolveMarket(resolvedByMarket, 1).rpc();
+      const state = await marketState(resolvedByMarket);
+      expect(state.status).to.deep.equal({ resolved: {} });
+      expect(state.winningOutcome.toNumber()).to.equal(2);
+    });
+  });
+
+  function bondAccounts(m: TestMarket, receiver: anchor.web3.PublicKey) {
+    return {
+      market: m.market,
+      marketAuthPda: m.marketAuthPda,
+      bondVault: marketPda("bond_vault", m.market),
+      receiver,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+  }
+
+  function refundCreatorBond(m: TestMarket, receiver: anchor.web3.PublicKey) {
+    return EmberProgram.methods.refundCreatorBond().accounts(bondAccounts(m, receiver));
+  }
+
+  function slashCreatorBond(m: TestMarket, receiver: anchor.web3.PublicKey, signer: Wallet = providerWallet()) {
+    return EmberProgram.methods.slashCreatorBond().accounts({
+      signer: signer.kp.publicKey,
+      config: programConfig,
+      ...bondAccounts(m, receiver),
+    }).signers([signer.kp]);
+  }
+
+  function claimCreatorFees(m: TestMarket, creator: Wallet) {
+    return EmberProgram.methods.claimCreatorFees().accounts({
+      signer: creator.kp.publicKey,
+      market: m.market,
+      marketAuthPda: m.marketAuthPda,
+      feeVault: m.feeVault,
+      receiver: creator.quote,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([creator.kp]);
+  }
+
+  describe("market creators", () => {
+    let creator: Wallet;
+    let bonded: TestMarket;
+
+    // a market of a creator without admin rights, resolved by the market auth pda
+    before(async () => {
+      creator = await createWallet();
+      bonded = await createMarketWithCondition(creator, { marketResolves: true }, creator.quote);
+    });
+
+    it("Rejecting unbonded markets of non admins, high creator fees and early refunds", async () => {
+      await expectError(createMarketWithCondition(creator, { marketResolves: false }, creator.quote), "MarketResolverRequired");
+      await expectError(createMarketWithCondition(creator, { marketResolves: true }), "BondRequired");
+      await expectError(createMarketWithCondition(providerWallet(), { creatorFeeBps: 5_001 }), "InvalidCreatorFee");
+      await expectError(refundCreatorBond(bonded, creator.quote).rpc(), "BondNotRefundable");
+      await expectError(slashCreatorBond(bonded, creator.quote, creator).rpc(), "InvalidAdmin");
+    });
+
+    it("Posting the creator bond and getting it back once the market settled", async () => {
+      const before = await tokenAmount(creator.quote);
+      expect((await marketState(bonded)).bond.toNumber()).to.equal(100_000_000);
+
+      await resolveMarket(bonded, 0).rpc();
+      await settleMarket(bonded, 10).rpc();
+      await refundCreatorBond(bonded, creator.quote).rpc();
+      expect(await tokenAmount(creator.quote)).to.equal(before + 100_000_000);
+      expect((await marketState(bonded)).bond.toNumber()).to.equal(0);
+      await expectError(refundCreatorBond(bonded, creator.quote).rpc(), "NoBond");
+    });
+
+    it("Slashing the creator bond and closing the market", async () => {
+      const m = await createMarketWithCondition(creator, { marketResolves: true }, creator.quote);
+      const before = await tokenAmount(collateralTokenAta);
+      await slashCreatorBond(m, collateralTokenAta).rpc();
+      expect(await tokenAmount(collateralTokenAta)).to.equal(before + 100_000_000);
+      expect((await marketState(m)).status).to.deep.equal({ closed: {} });
+    });
+
+    it("Setting the creator's share of the fees aside on withdrawal", async () => {
+      const m = await createMarketWithCondition(providerWallet(), { creatorFeeBps: 2_000 });
+      await setMarketFees(m, 0, 100).rpc();
+      const maker = await joinMarket(m, await createTrader());
+      const taker = await joinMarket(m, await createTrader());
+      await mintSets(m.cond, maker, maker, 10);
+      await deposit(m, maker, 0, 10).rpc();
+      await deposit(m, taker, 505).rpc();
+      await limitOrder(m, maker, 1, ASK, 50, 10).rpc();
+      await limitOrder(m, taker, 1, BID, 50, 10).rpc();
+
+      // 1 of the 5 in taker fees stays in the fee vault for the creator, also over repeated withdrawals
+      const before = await tokenAmount(collateralTokenAta);
+      await withdrawFees(m).rpc();
+      await withdrawFees(m).rpc();
+      expect(await tokenAmount(collateralTokenAta)).to.equal(before + 4);
+      expect((await marketState(m)).creatorFees.toNumber()).to.equal(1);
+
+      const creatorBefore = await tokenAmount(collateralTokenAta);
+      await claimCreatorFees(m, providerWallet()).rpc();
+      expect(await tokenAmount(collateralTokenAta)).to.equal(creatorBefore + 1);
+      expect(await tokenAmount(m.feeVault)).to.equal(0);
+    });
+  });
+
+  const ROLE_ADMIN = 1;
+  const ROLE_PAUSER = 4;
+
+  function addAdmin(admin: anchor.web3.PublicKey, roles: number, signer: Wallet | null = null) {
+    const builder = EmberProgram.methods.addAdmin(admin, new anchor.BN(roles)).accounts({
+      signer: signer ? signer.kp.publicKey : wallet,
+      config: programConfig,
+    });
+    return signer ? builder.signers([signer.kp]) : builder;
+  }
+
+  function removeAdmin(admin: anchor.web3.PublicKey) {
+    return EmberProgram.methods.removeAdmin(admin).accounts({
+      signer: wallet,
+      config: programConfig,
+    });
+  }
+
+  // the defaults of a new program config, but for the given params
+  function setProgramConfig(params: { takerFeeBps?: number; minMarketDuration?: number } = {}, signer: Wallet | null = null) {
+    const builder = EmberProgram.methods.setProgramConfig({
+      makerFeeBps: new anchor.BN(0),
+      takerFeeBps: new anchor.BN(params.takerFeeBps ?? 0),
+      referralShareBps: new anchor.BN(0),
+      creatorBond: new anchor.BN(100_000_000),
+      maxCreatorFeeBps: new anchor.BN(5_000),
+      minMarketDuration: new anchor.BN(params.minMarketDuration ?? 3_600),
+    }).accounts({
+      signer: signer ? signer.kp.publicKey : wallet,
+      config: programConfig,
+    });
+    return signer ? builder.signers([signer.kp]) : builder;
+  }
+
+  function setMarketPaused(m: TestMarket, paused: boolean, signer: Wallet | null = null) {
+    const builder = EmberProgram.methods.setMarketPaused(paused).accounts({
+      signer: signer ? signer.kp.publicKey : wallet,
+      config: programConfig,
+      market: m.market,
+    });
+    return signer ? builder.signers([signer.kp]) : builder;
+  }
+
+  describe("program config", () => {
+    let pauser: Wallet;
+    let m: TestMarket;
+    let t: MarketTrader;
+
+    // a wallet with the pauser role only
+    before(async () => {
+      pauser = await createWallet();
+      await addAdmin(pauser.kp.publicKey, ROLE_PAUSER).rpc();
+      m = await createMarket();
+      t = await joinMarket(m, await createTrader());
+      await deposit(m, t, 100).rpc();
+    });
+
+    it("Rejecting config changes that leave no admin, set unknown roles or lack the role", async () => {
+      await expectError(removeAdmin(wallet).rpc(), "LastAdmin");
+      // replacing the roles of the only admin can't drop the admin role either
+      await expectError(addAdmin(wallet, ROLE_PAUSER).rpc(), "LastAdmin");
+      await expectError(addAdmin(new anchor.web3.Keypair().publicKey, 16).rpc(), "InvalidRoles");
+      await expectError(addAdmin(new anchor.web3.Keypair().publicKey, 0).rpc(), "InvalidRoles");
+      await expectError(removeAdmin(new anchor.web3.Keypair().publicKey).rpc(), "AdminNotFound");
+
+      await expectError(addAdmin(pauser.kp.publicKey, ROLE_ADMIN, pauser).rpc(), "InvalidAdmin");
+      await expectError(setMarketFees(m, 0, 100, pauser).rpc(), "InvalidAdmin");
+      await expectError(setProgramConfig({}, pauser).rpc(), "InvalidAdmin");
+    });
+
+    it("Pausing and resuming trading with the pauser role", async () => {
+      const admins = (await EmberProgram.account.programConfig.fetch(programConfig)).admins;
+      const added = admins.filter((admin) => admin.key.equals(pauser.kp.publicKey));
+      expect(added.length).to.equal(1);
+      expect(added[0].roles.toNumber()).to.equal(ROLE_PAUSER);
+
+      await setMarketPaused(m, true, pauser).rpc();
+      expect((await marketState(m)).paused).to.be.true;
+      await expectError(limitOrder(m, t, 1, BID, 40, 1).rpc(), "MarketClosed");
+      await setMarketPaused(m, false, pauser).rpc();
+      await limitOrder(m, t, 1, BID, 40, 1).rpc();
+      expect(await orderCount(m.orderbook1)).to.deep.equal({ bids: 1, asks: 0 });
+
+      await removeAdmin(pauser.kp.publicKey).rpc();
+      await expectError(setMarketPaused(m, true, pauser).rpc(), "InvalidAdmin");
+    });
+
+    it("Creating markets with the fees and minimum duration of the config", async () => {
+      await setProgramConfig({ takerFeeBps: 100, minMarketDuration: 0 }).rpc();
+      try {
+        const short = await createMarket([1, 1, 1], Math.floor(Date.now() / 1000) + 60);
+        expect((await marketState(short)).takerFeeBps.toNumber()).to.equal(100);
+      } finally {
+        await setProgramConfig().rpc();
+      }
+      // existing markets keep their fees
+      expect((await marketState(m)).takerFeeBps.toNumber()).to.equal(0);
+    });
+  });
 });
 